@@ -0,0 +1,65 @@
+//! The best score, and the run which made it.
+//!
+//! The best score is kept in a text file in the user's data directory (`$XDG_DATA_HOME/flappy`,
+//! or `~/.local/share/flappy`), holding nothing but the number. The run is kept next to it, as a
+//! run file (see `ghost`).
+
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read, Write};
+use std::path::PathBuf;
+
+use ghost::Run;
+
+/// The name of the directory of the game in the data directory.
+const DATA_DIR: &'static str = "flappy";
+/// The name of the best-score file.
+const BEST_FILE: &'static str = "best";
+/// The name of the file of the best run.
+const RUN_FILE: &'static str = "best-run";
+
+/// Get the path of a file in the data directory.
+///
+/// This is in the current directory if there is no data directory.
+fn path(name: &str) -> PathBuf {
+    let mut path = env::var_os("XDG_DATA_HOME").map(PathBuf::from).or_else(|| {
+        env::var_os("HOME").map(|home| PathBuf::from(home).join(".local").join("share"))
+    }).unwrap_or_default();
+
+    path.push(DATA_DIR);
+    path.push(name);
+    path
+}
+
+/// Load the best score.
+///
+/// A missing or unreadable file means there is no best score yet, so this never fails.
+pub fn load() -> u32 {
+    let mut text = String::new();
+    File::open(path(BEST_FILE))
+        .and_then(|mut file| file.read_to_string(&mut text))
+        .ok()
+        .and_then(|_| text.trim().parse().ok())
+        .unwrap_or(0)
+}
+
+/// Load the best run.
+///
+/// Returns None if there is none, or it cannot be read.
+pub fn load_run() -> Option<Run> {
+    File::open(path(RUN_FILE)).ok().and_then(|file| Run::read(BufReader::new(file)))
+}
+
+/// Write the best score and the run which made it, creating their directory if need be.
+pub fn save(best: u32, run: &Run) -> io::Result<()> {
+    let path = path(BEST_FILE);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let mut file = File::create(path)?;
+    writeln!(file, "{}", best)?;
+    file.flush()?;
+
+    run.write(File::create(self::path(RUN_FILE))?)
+}
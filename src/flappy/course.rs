@@ -0,0 +1,144 @@
+//! The course, an endless row of walls.
+//!
+//! Every wall has a gap for the bird to fly through. The walls are generated one at a time from a
+//! seeded random number generator, so the same seed always gives the same course. The further the
+//! bird gets, the narrower the gaps and the further they move from one wall to the next, but
+//! never so far that the bird could not make it.
+
+use extra::rand::Randomizer;
+
+use world::CLIMB;
+
+/// The height of the field of play.
+pub const HEIGHT: usize = 30;
+/// The distance between the walls.
+pub const WALL_SEP: usize = 15;
+/// The gap at the start of the course.
+const MAX_GAP: usize = 12;
+/// The narrowest gap the course ends up with.
+const MIN_GAP: usize = 6;
+/// The distance it takes for the gap to narrow by a row.
+const NARROW_EVERY: usize = 150;
+/// How far the gap moves from one wall to the next at the start of the course.
+const MIN_SHIFT: usize = 2;
+/// How far the gap moves at most from one wall to the next.
+///
+/// This is half of what the bird climbs between two walls, jumping all the way, which leaves it
+/// time to line up with the gap. Falling is always fast enough.
+const MAX_SHIFT: usize = (CLIMB * (WALL_SEP + 1) as f32 / 2.0) as usize;
+/// The distance it takes for the gap to move a row further.
+const SHIFT_EVERY: usize = 100;
+/// The rows kept free of gaps at the top and bottom of the field.
+const MARGIN: usize = 1;
+
+/// A wall, with a gap in it.
+#[derive(Copy, Clone, Debug)]
+pub struct Wall {
+    /// The number of rows in the gap.
+    pub gap_width: usize,
+    /// The top row of the gap.
+    pub gap_start: usize,
+}
+
+impl Wall {
+    /// Get the row below the gap.
+    pub fn gap_end(&self) -> usize {
+        self.gap_start + self.gap_width
+    }
+}
+
+/// The generator of the walls.
+pub struct Course {
+    /// The random number generator, seeded.
+    rand: Randomizer,
+    /// The last wall generated, if any.
+    last: Option<Wall>,
+}
+
+impl Course {
+    /// Start a course from a seed.
+    pub fn new(seed: u64) -> Course {
+        Course {
+            rand: Randomizer::new(seed),
+            last: None,
+        }
+    }
+
+    /// Generate the next wall, which is _distance_ columns into the course.
+    pub fn next_wall(&mut self, distance: usize) -> Wall {
+        let gap_width = gap_width(distance);
+        let highest = MARGIN;
+        let lowest = HEIGHT - MARGIN - gap_width;
+
+        // The first gap is in the middle, where the bird starts.
+        let previous = self.last.map_or((HEIGHT - gap_width) / 2, |wall| wall.gap_start);
+        let shift = max_shift(distance);
+        let offset = self.rand.read_u8() as usize % (2 * shift + 1);
+
+        // Move the gap by up to the shift either way, staying on the field.
+        let gap_start = (previous + offset).max(highest + shift).min(lowest + shift) - shift;
+
+        let wall = Wall {
+            gap_width: gap_width,
+            gap_start: gap_start,
+        };
+        self.last = Some(wall);
+        wall
+    }
+}
+
+/// Get the width of the gaps, _distance_ columns into the course.
+pub fn gap_width(distance: usize) -> usize {
+    MAX_GAP.saturating_sub(distance / NARROW_EVERY).max(MIN_GAP)
+}
+
+/// Get how far the gaps move at most from one wall to the next, _distance_ columns into the
+/// course.
+pub fn max_shift(distance: usize) -> usize {
+    (MIN_SHIFT + distance / SHIFT_EVERY).min(MAX_SHIFT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use world::{World, DEFAULT_RATE};
+
+    /// Get how far up the bird gets between two walls, jumping as often as it can.
+    fn climb() -> f32 {
+        let mut world = World::new(0, DEFAULT_RATE);
+        let start = world.height;
+        while world.distance() < (WALL_SEP + 1) as f32 {
+            world.step(true);
+        }
+
+        start - world.height
+    }
+
+    #[test]
+    fn gaps_stay_in_reach() {
+        let climb = climb();
+        assert!(MAX_SHIFT as f32 <= climb, "the bird only climbs {} rows", climb);
+
+        for seed in 0..20 {
+            let mut course = Course::new(seed);
+            let mut previous: Option<Wall> = None;
+
+            // The walls are laid out like the world does, from column 30 on.
+            for i in 0..1000 {
+                let distance = 30 + i * (WALL_SEP + 1);
+                let wall = course.next_wall(distance);
+
+                assert_eq!(wall.gap_width, gap_width(distance));
+                assert!(wall.gap_start >= MARGIN);
+                assert!(wall.gap_end() <= HEIGHT - MARGIN);
+
+                if let Some(previous) = previous {
+                    let shift = (wall.gap_start as isize - previous.gap_start as isize).abs();
+                    assert!(shift as usize <= max_shift(distance), "seed {}, wall {}", seed, i);
+                    assert!(shift as f32 <= climb);
+                }
+                previous = Some(wall);
+            }
+        }
+    }
+}
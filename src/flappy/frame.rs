@@ -0,0 +1,177 @@
+//! The frame buffer.
+//!
+//! Everything on the screen is drawn into a frame first, one character per cell. Rendering the
+//! frame only writes the cells which changed since it was last rendered, so nothing flickers.
+
+use std::io::{self, Write};
+
+use termion::{color, cursor};
+
+/// A character on the screen, and whether it is drawn faint.
+type Cell = (char, bool);
+
+/// A grid of characters, along with what the terminal shows of it.
+pub struct Frame {
+    /// The width of the frame.
+    pub width: usize,
+    /// The height of the frame.
+    pub height: usize,
+    /// The cells, row by row.
+    cells: Vec<Cell>,
+    /// The cells as last rendered, row by row, or None where the terminal is not known to show
+    /// anything in particular.
+    shown: Vec<Option<Cell>>,
+}
+
+impl Frame {
+    /// Create a blank frame, of which nothing is shown yet.
+    pub fn new(width: usize, height: usize) -> Frame {
+        Frame {
+            width: width,
+            height: height,
+            cells: vec![(' ', false); width * height],
+            shown: vec![None; width * height],
+        }
+    }
+
+    /// Blank every cell, to draw the next frame from scratch.
+    pub fn clear(&mut self) {
+        for cell in self.cells.iter_mut() {
+            *cell = (' ', false);
+        }
+    }
+
+    /// Forget what the terminal shows, so the next render writes every cell.
+    ///
+    /// This is needed after drawing anything over the frame, such as a clear screen.
+    pub fn invalidate(&mut self) {
+        for cell in self.shown.iter_mut() {
+            *cell = None;
+        }
+    }
+
+    /// Put a character at (x, y). Anything outside the frame is left out.
+    pub fn set(&mut self, x: usize, y: usize, c: char) {
+        if x < self.width && y < self.height {
+            self.cells[y * self.width + x] = (c, false);
+        }
+    }
+
+    /// Put a faint character at (x, y), for things which are not really there.
+    pub fn set_faint(&mut self, x: usize, y: usize, c: char) {
+        if x < self.width && y < self.height {
+            self.cells[y * self.width + x] = (c, true);
+        }
+    }
+
+    /// Write a text from (x, y) rightwards.
+    pub fn text(&mut self, x: usize, y: usize, text: &str) {
+        for (i, c) in text.chars().enumerate() {
+            self.set(x + i, y, c);
+        }
+    }
+
+    /// Write the cells which changed since the last render to the terminal.
+    pub fn render<W: Write>(&mut self, to: &mut W) -> io::Result<()> {
+        // Where the cursor is, if known; it moves along by itself after every character.
+        let mut at = None;
+        let mut faint = false;
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let i = y * self.width + x;
+                if self.shown[i] == Some(self.cells[i]) {
+                    continue;
+                }
+
+                if at != Some((x, y)) {
+                    write!(to, "{}", cursor::Goto(x as u16 + 1, y as u16 + 1))?;
+                }
+
+                let (c, cell_faint) = self.cells[i];
+                if cell_faint != faint {
+                    faint = cell_faint;
+                    if faint {
+                        write!(to, "{}", color::Fg(color::LightBlack))?;
+                    } else {
+                        write!(to, "{}", color::Fg(color::Reset))?;
+                    }
+                }
+                write!(to, "{}", c)?;
+
+                self.shown[i] = Some(self.cells[i]);
+                at = Some((x + 1, y));
+            }
+        }
+
+        if faint {
+            write!(to, "{}", color::Fg(color::Reset))?;
+        }
+
+        to.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A terminal keeping whatever is written to it, and counting the flushes.
+    struct Terminal {
+        written: Vec<u8>,
+        flushes: usize,
+    }
+
+    impl Write for Terminal {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.written.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.flushes += 1;
+            Ok(())
+        }
+    }
+
+    /// Render a frame, returning what was written.
+    fn render(frame: &mut Frame, terminal: &mut Terminal) -> String {
+        terminal.written.clear();
+        frame.render(terminal).unwrap();
+        String::from_utf8(terminal.written.clone()).unwrap()
+    }
+
+    #[test]
+    fn only_changes_are_written() {
+        let mut terminal = Terminal {
+            written: Vec::new(),
+            flushes: 0,
+        };
+        let mut frame = Frame::new(3, 2);
+        frame.text(0, 0, "ab");
+        frame.set_faint(2, 1, '.');
+
+        assert_eq!(render(&mut frame, &mut terminal),
+                   "\x1b[1;1Hab \x1b[2;1H  \x1b[38;5;8m.\x1b[39m");
+        assert_eq!(terminal.flushes, 1);
+
+        // Nothing changed, so there is nothing to write.
+        assert_eq!(render(&mut frame, &mut terminal), "");
+        assert_eq!(terminal.flushes, 2);
+
+        frame.set(1, 1, 'x');
+        assert_eq!(render(&mut frame, &mut terminal), format!("{}x", cursor::Goto(2, 2)));
+
+        // Drawing the same frame again from scratch changes nothing either.
+        frame.clear();
+        frame.text(0, 0, "ab");
+        frame.set(1, 1, 'x');
+        frame.set_faint(2, 1, '.');
+        assert_eq!(render(&mut frame, &mut terminal), "");
+
+        frame.invalidate();
+        assert_eq!(render(&mut frame, &mut terminal),
+                   format!("{}ab {} x{}.{}", cursor::Goto(1, 1), cursor::Goto(1, 2),
+                           color::Fg(color::LightBlack), color::Fg(color::Reset)));
+    }
+}
@@ -0,0 +1,194 @@
+//! Recording runs and playing them back as ghosts.
+//!
+//! The course and the physics only depend on the seed and on when the bird jumps, so a run is
+//! recorded as the seed, the rate of the physics and the steps at which jump was pressed. Stepping
+//! a new world through the same jumps gives the very same run. A run file looks like this:
+//!
+//! ```text
+//! flappy-run 1
+//! seed 1234
+//! rate 60
+//! 14
+//! 52
+//! 90
+//! ```
+//!
+//! with a line for every jump, giving the number of steps taken before it.
+
+use std::io::{self, BufRead, Write};
+use std::str::FromStr;
+
+use world::World;
+
+/// The first line of every run file.
+const MAGIC: &'static str = "flappy-run";
+/// The current version of the format.
+const VERSION: u32 = 1;
+
+/// A recorded run.
+#[derive(Clone)]
+pub struct Run {
+    /// The seed of the course.
+    pub seed: u64,
+    /// The number of physics steps per second.
+    pub rate: u32,
+    /// The steps at which jump was pressed, in order.
+    pub jumps: Vec<u64>,
+}
+
+impl Run {
+    /// Start recording a run in a world which was just created.
+    pub fn new(world: &World) -> Run {
+        Run {
+            seed: world.seed,
+            rate: world.rate,
+            jumps: Vec::new(),
+        }
+    }
+
+    /// Write the run file.
+    pub fn write<W: Write>(&self, mut to: W) -> io::Result<()> {
+        writeln!(to, "{} {}", MAGIC, VERSION)?;
+        writeln!(to, "seed {}", self.seed)?;
+        writeln!(to, "rate {}", self.rate)?;
+
+        for jump in self.jumps.iter() {
+            writeln!(to, "{}", jump)?;
+        }
+
+        to.flush()
+    }
+
+    /// Read a run file.
+    ///
+    /// Returns None if the file cannot be read, or is not a run.
+    pub fn read<R: BufRead>(from: R) -> Option<Run> {
+        let mut lines = from.lines().map(|line| line.ok());
+
+        if lines.next()?? != format!("{} {}", MAGIC, VERSION) {
+            return None;
+        }
+
+        let seed = field(&lines.next()??, "seed")?;
+        let rate = field(&lines.next()??, "rate")?;
+        if rate == 0 {
+            return None;
+        }
+
+        let mut jumps: Vec<u64> = Vec::new();
+        for line in lines {
+            let jump = line?.trim().parse().ok()?;
+            if jumps.last().map_or(false, |&last| jump <= last) {
+                return None;
+            }
+            jumps.push(jump);
+        }
+
+        Some(Run {
+            seed: seed,
+            rate: rate,
+            jumps: jumps,
+        })
+    }
+}
+
+/// Parse a line giving the value of a field, such as `seed 1234`.
+fn field<T: FromStr>(line: &str, name: &str) -> Option<T> {
+    let mut words = line.split_whitespace();
+    if words.next()? != name {
+        return None;
+    }
+
+    let value = words.next()?.parse().ok()?;
+    if words.next().is_some() {
+        return None;
+    }
+
+    Some(value)
+}
+
+/// A recorded run, playing back.
+pub struct Ghost {
+    /// The world of the run.
+    pub world: World,
+    /// The run.
+    run: Run,
+    /// The number of jumps made so far.
+    next: usize,
+}
+
+impl Ghost {
+    /// Start playing back a run.
+    pub fn new(run: Run) -> Ghost {
+        Ghost {
+            world: World::new(run.seed, run.rate),
+            run: run,
+            next: 0,
+        }
+    }
+
+    /// Move the run on by a step, unless the bird already crashed.
+    pub fn step(&mut self) {
+        if self.world.crashed() {
+            return;
+        }
+
+        let jump = self.run.jumps.get(self.next) == Some(&self.world.ticks);
+        if jump {
+            self.next += 1;
+        }
+
+        self.world.step(jump);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use world::{BIRD_SIZE, DEFAULT_RATE};
+
+    /// Fly a world for a while, pressing jump whenever the bird is below the middle of the next
+    /// gap, and record the run like the game does.
+    fn fly(seed: u64) -> (World, Run) {
+        let mut world = World::new(seed, DEFAULT_RATE);
+        let mut run = Run::new(&world);
+
+        while !world.crashed() && world.ticks < 3000 {
+            let target = world.walls.front().map_or(15.0, |wall| {
+                (wall.gap_start + wall.gap_end()) as f32 / 2.0
+            });
+            let jump = world.height + BIRD_SIZE > target;
+            if jump {
+                run.jumps.push(world.ticks);
+            }
+            world.step(jump);
+        }
+
+        (world, run)
+    }
+
+    #[test]
+    fn ghost_flies_the_recorded_run() {
+        for seed in 0..5 {
+            let (world, run) = fly(seed);
+            assert!(world.score > 0);
+
+            let mut file = Vec::new();
+            run.write(&mut file).unwrap();
+            let read = Run::read(&file[..]).unwrap();
+            assert_eq!((read.seed, read.rate), (seed, DEFAULT_RATE));
+            assert_eq!(read.jumps, run.jumps);
+
+            let mut ghost = Ghost::new(read);
+            while ghost.world.ticks < world.ticks && !ghost.world.crashed() {
+                ghost.step();
+            }
+
+            assert_eq!(ghost.world.ticks, world.ticks);
+            assert_eq!(ghost.world.distance(), world.distance());
+            assert_eq!(ghost.world.height, world.height);
+            assert_eq!(ghost.world.score, world.score);
+            assert_eq!(ghost.world.crashed(), world.crashed());
+        }
+    }
+}
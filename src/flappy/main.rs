@@ -1,84 +1,161 @@
 /// TODO:
-/// * Randomly generated walls
 /// * User-defined width and height
 /// * Tune speed, walls for better gameplay
 extern crate termion;
+extern crate extra;
 use termion::{async_stdin, clear, cursor, style};
 use termion::raw::{IntoRawMode, RawTerminal};
 
 use std::collections::VecDeque;
 
+use std::env;
 use std::io::{self, Write, Read};
+use std::process;
 use std::thread;
-use std::time::Duration;
-
-
-const BIRD_CHAR: u8 = b'@';
-const WALL_CHAR: u8 = b'#';
-/// The rate of change of the bird's velocity
-const GRAVITY: f32 = 0.75;
-/// The width and height of the field of play
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use course::{Wall, HEIGHT, WALL_SEP};
+use frame::Frame;
+use ghost::{Ghost, Run};
+use world::{World, BIRD_SIZE};
+
+mod best;
+mod course;
+mod frame;
+mod ghost;
+mod world;
+
+
+/// The bird, in the upper or the lower half of a row
+const BIRD_UPPER_CHAR: char = '▀';
+const BIRD_LOWER_CHAR: char = '▄';
+/// A wall filling a column, or the left or right half of it
+const WALL_CHAR: char = '█';
+const WALL_LEFT_CHAR: char = '▌';
+const WALL_RIGHT_CHAR: char = '▐';
+const GROUND_CHAR: char = '█';
+/// The width of the field of play
 const WIDTH: usize = 80;
-const HEIGHT: usize = 30;
-/// The distance between the walls
-const WALL_SEP: usize = 15;
+/// The time between two frames, in milliseconds
+const FRAME_TIME: u64 = 16;
+/// How long to wait between looks at the keyboard, in milliseconds, when there is nothing else to
+/// do
+const POLL_INTERVAL: u64 = 10;
+/// The time the physics catches up on at most after falling behind, in milliseconds
+const MAX_LAG: u64 = 250;
+/// The time the game over box ignores the keyboard for, in milliseconds, so that jumps pressed
+/// just before crashing do not retry right away
+const RETRY_DELAY: u64 = 500;
 
 const WELCOME_SCREEN: &'static str = "+=====================+\n\r\
                                       | space to jump       |\n\r\
                                       | q     to quit       |\n\r\
                                       +=====================+";
 
+/// The box over the field once the bird crashed, with the score and the best score filled in
+const GAME_OVER: [&'static str; 6] = ["+=========================+",
+                                      "| GAME OVER               |",
+                                      "| score        best       |",
+                                      "| space to retry          |",
+                                      "| q     to quit           |",
+                                      "+=========================+"];
+const NEW_BEST: &'static str = "GAME OVER, NEW BEST!";
+
+const HELP: &'static str = r#"
+flappy ~ fly through the gaps in the walls.
+
+flags:
+    --seed N    ~ lay out the course from the seed N, so it is the same every time (a new one
+                  every game by default).
+    --rate N    ~ run the physics N times a second, from 10 to 1000 (60 by default). Higher
+                  rates are more precise, but the game plays the same.
+    --no-ghost  ~ fly alone, on a new course every game. By default, the ghost of the best
+                  run flies along, on its course (if it is the one given with --seed, and at
+                  the same rate).
+    -h | --help ~ this help page.
+
+rules:
+    Every wall passed scores a point. The best score is kept between games, along with the
+    run which made it, to race against.
+
+    The bird only jumps once it is falling: a jump pressed on the way up is lost. Jumping as
+    soon as it falls, the bird climbs about a row per column flown, which is always enough to
+    reach the next gap.
+
+controls:
+    space ~ jump (once falling), or retry once the game is over.
+    q     ~ quit.
+"#;
+
 
 /// A flappy bird game
 struct Game<R, W: Write> {
-    /// The walls currently on the screen
-    walls: VecDeque<Wall>,
-    closest_wall: usize,
-    /// The current position and vertical velocity of the bird
-    height: f32,
-    velocity: f32,
-    /// The distance traveled
-    distance: usize,
+    /// The bird and the walls
+    world: World,
+    /// Was jump pressed since the last step?
+    jump: bool,
+    /// The seed of every course, if it is not a new one every game
+    seed: Option<u64>,
+    /// The best score so far
+    best: u32,
+    /// The run being played, as recorded so far
+    run: Run,
+    /// Is the best run raced against?
+    race: bool,
+    /// The best run, if it is raced against
+    best_run: Option<Run>,
+    /// The best run, playing back alongside the bird, if it is on the same course
+    ghost: Option<Ghost>,
+    /// The screen, drawn into before it is shown
+    frame: Frame,
     /// Input/Output
     stdout: W,
     stdin: R,
 }
 
 
-struct Wall {
-    pub gap_width: usize,
-    pub gap_start: usize,
-}
-
-impl Wall {
-    fn new(wid: usize, start: usize) -> Self {
-        Wall {
-            gap_width: wid,
-            gap_start: start,
-        }
-    }
-}
-
-
 impl<R: Read, W: Write> Game<R, W> {
-    /// Construct the default game
-    fn new(stdin: R, stdout: W) -> Game<R, RawTerminal<W>> {
-        Game {
-            walls: vec![Wall::new(10, 5),
-                        Wall::new(8, 4),
-                        Wall::new(12, 14),
-                        Wall::new(8, 10),
-                        Wall::new(8, 7),
-                        Wall::new(7, 8)]
-                       .into_iter()
-                       .collect(),
-            closest_wall: 30,
-            height: 15.0,
-            velocity: 0.0,
-            distance: 0,
+    /// Construct a game on the course laid out from the seed, or a new course every game if
+    /// there is none, with the physics running _rate_ times a second
+    ///
+    /// With _ghost_ set, the best run flies along as a ghost.
+    fn new(stdin: R, stdout: W, seed: Option<u64>, rate: u32, ghost: bool) -> Game<R, RawTerminal<W>> {
+        let world = World::new(seed.unwrap_or_else(clock_seed), rate);
+
+        let mut game = Game {
+            run: Run::new(&world),
+            world: world,
+            jump: false,
+            seed: seed,
+            best: best::load(),
+            race: ghost,
+            best_run: if ghost { best::load_run() } else { None },
+            ghost: None,
+            frame: Frame::new(WIDTH, HEIGHT + 2),
             stdout: stdout.into_raw_mode().unwrap(),
             stdin: stdin,
-        }
+        };
+
+        game.restart();
+        game
+    }
+
+    /// Start a new game
+    ///
+    /// This is on the course of the best run, if it is raced against, unless the seed is fixed
+    /// to another one. Otherwise, it is on a new course unless the seed is fixed.
+    fn restart(&mut self) {
+        let rate = self.world.rate;
+        let seed = self.seed
+            .or_else(|| self.best_run.as_ref().map(|run| run.seed))
+            .unwrap_or_else(clock_seed);
+
+        self.world = World::new(seed, rate);
+        self.run = Run::new(&self.world);
+        self.ghost = self.best_run.as_ref()
+            .filter(|run| run.seed == seed && run.rate == rate)
+            .map(|run| Ghost::new(run.clone()));
+        self.jump = false;
     }
 
     /// Play the game until it is over
@@ -88,155 +165,300 @@ impl<R: Read, W: Write> Game<R, W> {
         write!(self.stdout, "{}{}{}{}{}", clear::All, style::Reset, cursor::Goto(1, 1), cursor::Hide, WELCOME_SCREEN).unwrap();
         self.stdout.flush().unwrap();
 
-        loop {
-            let mut b = [0];
-            if self.stdin.read(&mut b).is_ok() {
-                if b[0] != 0 {
-                    break;
-                }
-            }
+        self.wait_key();
+
+        // The welcome screen is in the way of the frame.
+        write!(self.stdout, "{}", clear::All).unwrap();
+        self.frame.invalidate();
+
+        while self.play() && self.game_over() {
+            self.restart();
         }
+    }
+
+    /// Play until the bird crashes.
+    ///
+    /// Returns false if the game is supposed to be closed.
+    fn play(&mut self) -> bool {
+        // The physics steps at a fixed rate, however often a frame is drawn. The lag is the
+        // time passed which has not been stepped through yet.
+        let tick = Duration::from_secs(1) / self.world.rate;
+        let mut before = Instant::now();
+        let mut lag = Duration::from_millis(0);
 
         loop {
-            let mut b = [0];
-            if self.stdin.read(&mut b).is_ok() {
-                match b[0] {
-                    b'q' => break,
-                    b' ' => self.jump(),
+            let mut buf = [0; 16];
+            let n = self.stdin.read(&mut buf).unwrap_or(0);
+            for &b in buf[..n].iter() {
+                match b {
+                    b'q' => return false,
+                    b' ' => self.jump = true,
                     _ => {}
                 };
             }
 
-            thread::sleep(Duration::from_millis(150));
-            self.step();
-            self.draw();
+            let now = Instant::now();
+            lag = (lag + now.duration_since(before)).min(Duration::from_millis(MAX_LAG));
+            before = now;
 
-            if self.game_over() {
-                break;
-            }
-        }
-    }
+            while lag >= tick {
+                lag -= tick;
 
+                // A jump takes effect on the next step.
+                let jump = self.jump;
+                self.jump = false;
+                if jump {
+                    self.run.jumps.push(self.world.ticks);
+                }
+                self.world.step(jump);
 
-    fn step(&mut self) {
-        self.height -= self.velocity;
-        self.velocity -= GRAVITY;
-        if self.closest_wall == 0 {
-            if let Some(wall) = self.walls.pop_front() {
-                self.walls.push_back(wall);
+                if let Some(ref mut ghost) = self.ghost {
+                    ghost.step();
+                }
+
+                if self.world.crashed() {
+                    self.draw();
+                    return true;
+                }
             }
-            self.closest_wall = WALL_SEP;
-        } else {
-            self.closest_wall -= 1;
+
+            self.draw();
+            thread::sleep(Duration::from_millis(FRAME_TIME));
         }
-        self.distance += 1;
     }
 
 
+    /// Draw the game into the frame, and show what changed on the screen
     fn draw(&mut self) {
-        write!(self.stdout, "{}{}", clear::All, cursor::Goto(1, 1)).unwrap();
-        for _ in 0..WIDTH {
-            self.stdout.write(&[WALL_CHAR]).unwrap();
+        self.frame.clear();
+
+        // The layers, back to front.
+        draw_ground(&mut self.frame);
+        draw_walls(&mut self.frame, &self.world.walls, self.world.closest_wall());
+        if let Some(ref ghost) = self.ghost {
+            if !ghost.world.crashed() {
+                draw_ghost(&mut self.frame, ghost.world.height);
+            }
         }
-        self.stdout.write(b"\n\r").unwrap();
-
-        for row in 0..HEIGHT {
-            let first_wall = if self.height as usize == row {
-                self.stdout.write(&[BIRD_CHAR]).unwrap();
-                if self.closest_wall == 0 {
-                    0
-                } else {
-                    self.closest_wall - 1
-                }
-            } else {
-                self.closest_wall
-            };
+        draw_bird(&mut self.frame, self.world.height);
+        draw_score(&mut self.frame, self.world.score, self.best);
 
-            let mut col = 0;
+        self.frame.render(&mut self.stdout).unwrap();
+    }
 
-            for _ in 0..first_wall {
-                self.stdout.write(b" ").unwrap();
-                col += 1;
+    /// Show the score over the field, keeping it if it is the best one, and wait for the player
+    /// to retry or quit.
+    ///
+    /// Returns true to retry.
+    fn game_over(&mut self) -> bool {
+        let score = self.world.score;
+        let new_best = score > self.best;
+        let x = (WIDTH - GAME_OVER[0].len()) / 2;
+        let y = (HEIGHT + 2 - GAME_OVER.len()) / 2;
+
+        if new_best {
+            self.best = score;
+            if self.race {
+                self.best_run = Some(self.run.clone());
             }
-
-            if let Some(wall) = self.walls.get(0) {
-                if (row < wall.gap_start) || (row > wall.gap_start + wall.gap_width) {
-                    self.stdout.write(&[WALL_CHAR])
-                } else {
-                    self.stdout.write(b" ")
-                }
-                .unwrap();
-                col += 1;
+            if let Err(err) = best::save(score, &self.run) {
+                self.frame.text(x, y + GAME_OVER.len(), &format!(" could not save the best score: {} ", err));
             }
-
-            for wall in self.walls.iter().skip(1) {
-                col += WALL_SEP + 1;
-                if col >= WIDTH {
-                    break;
-                }
-                self.stdout.write(&[b' '; WALL_SEP]).unwrap();
-                if (row < wall.gap_start) || (row > wall.gap_start + wall.gap_width) {
-                    self.stdout.write(&[WALL_CHAR])
-                } else {
-                    self.stdout.write(b" ")
-                }
-                .unwrap();
-            }
-
-            self.stdout.write(b"\n\r").unwrap();
         }
 
-        for _ in 0..WIDTH {
-            self.stdout.write(&[WALL_CHAR]).unwrap();
+        for (i, line) in GAME_OVER.iter().enumerate() {
+            self.frame.text(x, y + i, line);
         }
-        self.stdout.write(b"\n\r").unwrap();
-    }
+        if new_best {
+            self.frame.text(x + 2, y + 1, NEW_BEST);
+        }
+        self.frame.text(x + 8, y + 2, &score.to_string());
+        self.frame.text(x + 20, y + 2, &self.best.to_string());
+        self.frame.render(&mut self.stdout).unwrap();
 
+        thread::sleep(Duration::from_millis(RETRY_DELAY));
+        self.drain_input();
 
-    fn game_over(&self) -> bool {
-        if self.closest_wall == 0 {
-            if let Some(wall) = self.walls.get(0) {
-                let height = self.height as usize;
-                // If the bird is in contact with the wall
-                height < wall.gap_start || height >= (wall.gap_start + wall.gap_width)
-            } else {
-                false
+        loop {
+            match self.wait_key() {
+                b' ' => return true,
+                b'q' => return false,
+                _ => {},
             }
-        } else {
-            // If the bird is out of bounds
-            self.height < 0.0 || self.height >= HEIGHT as f32
         }
     }
 
-    /// Perform a jump.
-    fn jump(&mut self) {
-        if self.velocity < 0.0 {
-            self.velocity = 2.5;
+    /// Wait for a key to be pressed, and get its first byte.
+    fn wait_key(&mut self) -> u8 {
+        loop {
+            let mut b = [0];
+            if self.stdin.read(&mut b).unwrap_or(0) == 1 && b[0] != 0 {
+                return b[0];
+            }
+
+            thread::sleep(Duration::from_millis(POLL_INTERVAL));
         }
     }
 
+    /// Throw away the keys pressed so far.
+    fn drain_input(&mut self) {
+        let mut buf = [0; 64];
+        while self.stdin.read(&mut buf).unwrap_or(0) > 0 {}
+    }
+
     /// Initializes the screen
     fn init(&mut self) {
         write!(self.stdout, "{}{}", clear::All, cursor::Goto(1, 1)).unwrap();
     }
 }
 
+/// Draw the ground, above and below the field of play.
+fn draw_ground(frame: &mut Frame) {
+    let bottom = frame.height - 1;
+    for x in 0..frame.width {
+        frame.set(x, 0, GROUND_CHAR);
+        frame.set(x, bottom, GROUND_CHAR);
+    }
+}
+
+/// Draw the walls, the closest one being _closest_wall_ columns ahead of the bird.
+///
+/// A wall halfway between two columns is drawn as the halves of both.
+fn draw_walls(frame: &mut Frame, walls: &VecDeque<Wall>, closest_wall: f32) {
+    for (i, wall) in walls.iter().enumerate() {
+        let x = closest_wall + (i * (WALL_SEP + 1)) as f32;
+        let half = (x * 2.0).floor() as i32;
+
+        // The left of the field is column 0, so anything further left is left out.
+        let cells = if half % 2 == 0 {
+            vec![(half / 2, WALL_CHAR)]
+        } else {
+            vec![((half - 1) / 2, WALL_RIGHT_CHAR), ((half + 1) / 2, WALL_LEFT_CHAR)]
+        };
+
+        for &(column, c) in cells.iter().filter(|&&(column, _)| column >= 0) {
+            for row in 0..HEIGHT {
+                if row < wall.gap_start || row >= wall.gap_end() {
+                    frame.set(column as usize, row + 1, c);
+                }
+            }
+        }
+    }
+}
+
+/// Draw the bird, in the first column, unless it is off the field.
+///
+/// The bird is half a row high, so it is in the upper or the lower half of a row.
+fn draw_bird(frame: &mut Frame, height: f32) {
+    if let Some((row, c)) = bird_cell(height) {
+        frame.set(0, row, c);
+    }
+}
+
+/// Draw the ghost of the best run, faint, where the bird would be.
+fn draw_ghost(frame: &mut Frame, height: f32) {
+    if let Some((row, c)) = bird_cell(height) {
+        frame.set_faint(0, row, c);
+    }
+}
+
+/// Get the row of the frame a bird at _height_ is in, and its glyph, unless it is off the field.
+fn bird_cell(height: f32) -> Option<(usize, char)> {
+    if height >= 0.0 && height + BIRD_SIZE <= HEIGHT as f32 {
+        let half = (height * 2.0) as usize;
+        let c = if half % 2 == 0 { BIRD_UPPER_CHAR } else { BIRD_LOWER_CHAR };
+        Some((half / 2 + 1, c))
+    } else {
+        None
+    }
+}
+
+/// Draw the score and the best score, over the ground at the top.
+fn draw_score(frame: &mut Frame, score: u32, best: u32) {
+    frame.text(2, 0, &format!(" score {} ", score));
+
+    let best = format!(" best {} ", best);
+    let x = frame.width - 2 - best.chars().count();
+    frame.text(x, 0, &best);
+}
+
+/// Get a seed for a new course from the clock.
+fn clock_seed() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs() ^ time.subsec_nanos() as u64)
+        .unwrap_or(0)
+}
+
 
 impl<R, W: Write> Drop for Game<R, W> {
     fn drop(&mut self) {
         // When done, restore the defaults to avoid messing with the terminal.
         // (same as in ice and minesweeper)
         write!(self.stdout, "{}{}{}{}", clear::All, style::Reset, cursor::Goto(1, 1), cursor::Show).unwrap();
-        self.stdout.write(b"Distance traveled: ").unwrap();
-        self.stdout.write(self.distance.to_string().as_bytes()).unwrap();
-        self.stdout.write(b"\n\r").unwrap();
+        write!(self.stdout, "Score: {}, best: {}\n\r", self.world.score, self.best).unwrap();
     }
 }
 
 fn main() {
+    let mut args = env::args().skip(1);
+    let mut seed = None;
+    let mut rate = None;
+    let mut ghost = true;
+
     let stdout = io::stdout();
+    let stderr = io::stderr();
+    let mut stderr = stderr.lock();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--seed" => if seed.is_none() {
+                seed = Some(args.next().unwrap_or_else(|| {
+                    stderr.write(b"no seed given.\n").unwrap();
+                    stderr.flush().unwrap();
+                    process::exit(1);
+                }).parse().unwrap_or_else(|_| {
+                    stderr.write(b"invalid integer given.\n").unwrap();
+                    stderr.flush().unwrap();
+                    process::exit(1);
+                }));
+            } else {
+                stderr.write(b"you may only input one seed.\n").unwrap();
+                stderr.flush().unwrap();
+                process::exit(1);
+            },
+            "--rate" => if rate.is_none() {
+                rate = Some(args.next().unwrap_or_else(|| {
+                    stderr.write(b"no rate given.\n").unwrap();
+                    stderr.flush().unwrap();
+                    process::exit(1);
+                }).parse().ok().filter(|&rate| rate >= 10 && rate <= 1000).unwrap_or_else(|| {
+                    stderr.write(b"the rate must be an integer from 10 to 1000.\n").unwrap();
+                    stderr.flush().unwrap();
+                    process::exit(1);
+                }));
+            } else {
+                stderr.write(b"you may only input one rate.\n").unwrap();
+                stderr.flush().unwrap();
+                process::exit(1);
+            },
+            "--no-ghost" => ghost = false,
+            "-h" | "--help" => {
+                // Print the help page.
+                let mut stdout = stdout.lock();
+                stdout.write(HELP.as_bytes()).unwrap();
+                stdout.flush().unwrap();
+                process::exit(0);
+            },
+            _ => {
+                stderr.write(b"Unknown argument.\n").unwrap();
+                stderr.flush().unwrap();
+                process::exit(1);
+            },
+        }
+    }
 
-    let mut game = Game::new(async_stdin(), stdout.lock());
+    let rate = rate.unwrap_or(world::DEFAULT_RATE);
+    let mut game = Game::new(async_stdin(), stdout.lock(), seed, rate, ghost);
 
     game.start();
 }
@@ -0,0 +1,128 @@
+//! The game world: the bird, the walls and the physics moving them.
+//!
+//! The physics runs at a fixed rate, however fast the screen is drawn. Positions are measured in
+//! fractions of cells: the height of the bird in rows from the top of the field, and the distance
+//! in columns from the start of the course.
+
+use std::collections::VecDeque;
+
+use course::{Course, Wall, HEIGHT, WALL_SEP};
+
+/// The default number of physics steps per second.
+pub const DEFAULT_RATE: u32 = 60;
+/// The speed of the bird, in columns per second.
+const SPEED: f32 = 8.0;
+/// The rate of change of the bird's velocity, in rows per second squared.
+const GRAVITY: f32 = 33.0;
+/// The upwards velocity of a jump, in rows per second.
+const JUMP_SPEED: f32 = 14.0;
+/// How far the bird climbs at most, in rows per column, jumping every time it starts falling.
+///
+/// Since a jump is only taken once falling, the bird rises at half the jump speed on average.
+pub const CLIMB: f32 = JUMP_SPEED / 2.0 / SPEED;
+/// The height of the bird, in rows.
+pub const BIRD_SIZE: f32 = 0.5;
+/// The height of the bird at the start.
+const START_HEIGHT: f32 = 15.0;
+/// The column of the first wall.
+const FIRST_WALL: usize = 30;
+/// The number of walls kept ahead of the bird.
+const WALLS: usize = 6;
+
+/// The state of a game.
+pub struct World {
+    /// The walls ahead of the bird, closest first.
+    pub walls: VecDeque<Wall>,
+    /// The column of the closest wall.
+    first_wall: usize,
+    /// The seed of the course.
+    pub seed: u64,
+    /// The generator of the walls to come.
+    course: Course,
+    /// The height of the top of the bird, in rows from the top of the field.
+    pub height: f32,
+    /// The upwards velocity of the bird, in rows per second.
+    pub velocity: f32,
+    /// The number of walls passed.
+    pub score: u32,
+    /// The number of steps taken.
+    pub ticks: u64,
+    /// The number of steps per second.
+    pub rate: u32,
+}
+
+impl World {
+    /// Create the world at the start of the course laid out from the seed, with the physics
+    /// running _rate_ times a second.
+    pub fn new(seed: u64, rate: u32) -> World {
+        let mut course = Course::new(seed);
+        let walls = (0..WALLS)
+            .map(|i| course.next_wall(FIRST_WALL + i * (WALL_SEP + 1)))
+            .collect();
+
+        World {
+            walls: walls,
+            first_wall: FIRST_WALL,
+            seed: seed,
+            course: course,
+            height: START_HEIGHT,
+            velocity: 0.0,
+            score: 0,
+            ticks: 0,
+            rate: rate,
+        }
+    }
+
+    /// Get the distance traveled, in columns.
+    pub fn distance(&self) -> f32 {
+        self.ticks as f32 * SPEED / self.rate as f32
+    }
+
+    /// Get the distance from the bird to the closest wall, in columns.
+    pub fn closest_wall(&self) -> f32 {
+        self.first_wall as f32 - self.distance()
+    }
+
+    /// Move everything on by a step, making the bird jump first if _jump_ is set.
+    ///
+    /// The bird can only jump once it is falling.
+    pub fn step(&mut self, jump: bool) {
+        let dt = 1.0 / self.rate as f32;
+
+        if jump && self.velocity < 0.0 {
+            self.velocity = JUMP_SPEED;
+        }
+
+        self.height -= self.velocity * dt;
+        self.velocity -= GRAVITY * dt;
+        self.ticks += 1;
+
+        if self.closest_wall() <= -1.0 {
+            // The wall passed makes way for a new one at the end of the row.
+            self.walls.pop_front();
+            self.score += 1;
+            self.first_wall += WALL_SEP + 1;
+            let column = self.first_wall + (WALLS - 1) * (WALL_SEP + 1);
+            self.walls.push_back(self.course.next_wall(column));
+        }
+    }
+
+    /// Has the bird run into a wall, or left the field?
+    pub fn crashed(&self) -> bool {
+        let top = self.height;
+        let bottom = self.height + BIRD_SIZE;
+        if top < 0.0 || bottom > HEIGHT as f32 {
+            return true;
+        }
+
+        // The bird and the walls are a column wide, so they overlap while the wall is less than
+        // a column away either way.
+        let closest_wall = self.closest_wall();
+        match self.walls.front() {
+            Some(wall) if closest_wall > -1.0 && closest_wall < 1.0 => {
+                top < wall.gap_start as f32 || bottom > wall.gap_end() as f32
+            },
+            _ => false,
+        }
+    }
+}
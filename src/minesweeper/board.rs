@@ -0,0 +1,581 @@
+//! The board model.
+//!
+//! The board holds the state of a game and the rules acting on it, without drawing anything.
+//! Every action returns the cells it changed, so that the front end only has to draw those
+//! again.
+
+use std::cmp;
+
+use extra::rand::Randomizer;
+
+use code::BoardCode;
+use rand_u64;
+use solver::Solver;
+use topology::Topology;
+
+/// The number of boards rolled in no-guess mode, before falling back to repairing the last one.
+const MAX_REROLLS: usize = 100;
+
+/// A cell in the grid.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub struct Cell {
+    /// Does it contain a mine?
+    pub mine: bool,
+    /// Is it revealed?
+    ///
+    /// That is, is it showed or chosen previously by the player?
+    pub revealed: bool,
+    /// Does this flag contain a flag?
+    pub flagged: bool,
+    /// Does this cell contain a question mark?
+    ///
+    /// Unlike flags, question marks are only a note to the player, and the rules ignore them. A
+    /// cell never holds both a flag and a question mark.
+    pub questioned: bool,
+}
+
+impl Cell {
+    /// A concealed cell, with no mine placed yet.
+    pub fn new() -> Cell {
+        Cell {
+            mine: false,
+            revealed: false,
+            flagged: false,
+            questioned: false,
+        }
+    }
+}
+
+/// The state of a game.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum State {
+    /// The game goes on.
+    Playing,
+    /// Every cell without a mine is revealed.
+    Won,
+    /// The mine at (x, y) was hit.
+    Lost(u16, u16),
+}
+
+/// The board.
+pub struct Board {
+    /// Width of the grid.
+    width: u16,
+    /// The grid.
+    ///
+    /// The cells are enumerated like you would read a book. Left to right, until you reach the
+    /// line ending.
+    grid: Box<[Cell]>,
+    /// The number of mines to place.
+    mines: u32,
+    /// The shape of the board.
+    topology: Topology,
+    /// Only generate boards solvable without guessing?
+    no_guess: bool,
+    /// Points.
+    ///
+    /// That is, revealed fields.
+    points: u16,
+    /// The mine that was hit, if any.
+    exploded: Option<(u16, u16)>,
+    /// The code of the board, once its mines are placed.
+    code: Option<BoardCode>,
+}
+
+impl Board {
+    /// Create a board of the given size, with every cell concealed and no mines placed.
+    pub fn new(width: u16, height: u16, mines: u32, topology: Topology, no_guess: bool) -> Board {
+        Board {
+            width: width,
+            grid: vec![Cell::new(); width as usize * height as usize].into_boxed_slice(),
+            mines: mines,
+            topology: topology,
+            no_guess: no_guess,
+            points: 0,
+            exploded: None,
+            code: None,
+        }
+    }
+
+    /// Get the width (number of columns) of the grid.
+    pub fn width(&self) -> u16 {
+        self.width
+    }
+
+    /// Calculate the height (number of rows) of the grid.
+    pub fn height(&self) -> u16 {
+        (self.grid.len() / self.width as usize) as u16
+    }
+
+    /// Get the number of mines to place.
+    pub fn mines(&self) -> u32 {
+        self.mines
+    }
+
+    /// Get the shape of the board.
+    pub fn topology(&self) -> Topology {
+        self.topology
+    }
+
+    /// Are boards generated in no-guess mode?
+    pub fn no_guess(&self) -> bool {
+        self.no_guess
+    }
+
+    /// Get the points.
+    pub fn points(&self) -> u16 {
+        self.points
+    }
+
+    /// Get the code of the board, if the mines are placed.
+    pub fn code(&self) -> Option<BoardCode> {
+        self.code
+    }
+
+    /// Get the cells, enumerated like the grid.
+    pub fn cells(&self) -> &[Cell] {
+        &self.grid
+    }
+
+    /// Get the grid position of a given coordinate.
+    pub fn pos(&self, x: u16, y: u16) -> usize {
+        y as usize * self.width as usize + x as usize
+    }
+
+    /// Get the coordinates of a given grid position.
+    pub fn coords(&self, c: usize) -> (u16, u16) {
+        ((c % self.width as usize) as u16, (c / self.width as usize) as u16)
+    }
+
+    /// Get the cell at (x, y).
+    pub fn cell(&self, x: u16, y: u16) -> Cell {
+        self.grid[self.pos(x, y)]
+    }
+
+    /// Calculate the adjacent cells, as given by the topology.
+    pub fn adjacent(&self, x: u16, y: u16) -> Vec<(u16, u16)> {
+        self.topology.neighbours(self.width, self.height(), x, y)
+    }
+
+    /// Calculate the neighbours of every cell, by position.
+    pub fn neighbours(&self) -> Vec<Vec<usize>> {
+        let mut neighbours = Vec::with_capacity(self.grid.len());
+        for y in 0..self.height() {
+            for x in 0..self.width {
+                neighbours.push(self.adjacent(x, y).iter().map(|&(x, y)| self.pos(x, y)).collect());
+            }
+        }
+        neighbours
+    }
+
+    /// Get the value of a cell.
+    ///
+    /// The value represent the sum of adjacent cells containing mines. A cell of value, 0, is
+    /// called "free".
+    pub fn value(&self, x: u16, y: u16) -> u8 {
+        self.adjacent(x, y).iter().filter(|&&(x, y)| self.cell(x, y).mine).count() as u8
+    }
+
+    /// Count the mines on the board.
+    ///
+    /// Before the mines are placed, this is the number to place. After, it is the number actually
+    /// placed, which can be lower if the grid is crowded.
+    pub fn count_mines(&self) -> u32 {
+        if self.started() {
+            self.grid.iter().filter(|cell| cell.mine).count() as u32
+        } else {
+            self.mines
+        }
+    }
+
+    /// Count the flags on the board.
+    pub fn count_flags(&self) -> u32 {
+        self.grid.iter().filter(|cell| cell.flagged).count() as u32
+    }
+
+    /// Are the mines placed yet?
+    pub fn started(&self) -> bool {
+        self.code.is_some()
+    }
+
+    /// Get the state of the game.
+    pub fn state(&self) -> State {
+        if let Some((x, y)) = self.exploded {
+            State::Lost(x, y)
+        } else if self.started() && self.grid.iter().all(|cell| cell.mine || cell.revealed) {
+            State::Won
+        } else {
+            State::Playing
+        }
+    }
+
+    /// Conceal every cell, and remove the mines.
+    pub fn clear(&mut self) {
+        for cell in self.grid.iter_mut() {
+            *cell = Cell::new();
+        }
+
+        self.points = 0;
+        self.exploded = None;
+        self.code = None;
+    }
+
+    /// Restore a saved grid, enumerated like `Board::cells`.
+    ///
+    /// The mines are placed if and only if there is a board code.
+    pub fn restore(&mut self, grid: Vec<Cell>, points: u16, code: Option<BoardCode>) {
+        self.grid = grid.into_boxed_slice();
+        self.points = points;
+        self.exploded = None;
+        self.code = code;
+    }
+
+    /// Put back the given cells (by position) and points, as they were before some actions.
+    ///
+    /// This takes back a fatal action too. Returns the cells which changed.
+    pub fn revert(&mut self, cells: &[(usize, Cell)], points: u16) -> Vec<(u16, u16)> {
+        for &(c, cell) in cells.iter() {
+            self.grid[c] = cell;
+        }
+
+        self.points = points;
+        self.exploded = None;
+        cells.iter().map(|&(c, _)| self.coords(c)).collect()
+    }
+
+    /// Roll a random mine layout, where the cell _start_ and its neighbours are free.
+    ///
+    /// The mines are spread over the other cells, as many of them as fit.
+    fn roll_mines(&self, rand: &mut Randomizer, start: usize, neighbours: &[Vec<usize>]) -> Vec<bool> {
+        let mut free: Vec<usize> = (0..self.grid.len())
+            .filter(|&c| c != start && !neighbours[start].contains(&c))
+            .collect();
+        let mut mines = vec![false; self.grid.len()];
+
+        // Shuffle the free cells just far enough to draw the mines.
+        for i in 0..cmp::min(self.mines as usize, free.len()) {
+            let j = i + rand_u64(rand) as usize % (free.len() - i);
+            free.swap(i, j);
+            mines[free[i]] = true;
+        }
+
+        mines
+    }
+
+    /// Place the mines from a seed, keeping the cell at (x, y) and its neighbours free.
+    ///
+    /// In no-guess mode, boards are re-rolled until the solver can clear one from (x, y). If
+    /// that fails too many times, the last board is repaired by moving (or, if there is nowhere
+    /// to go, removing) the mines that the solver gets stuck on.
+    ///
+    /// The placement only depends on the seed, the grid, and (x, y), which make up the board
+    /// code.
+    pub fn place_mines(&mut self, x: u16, y: u16, seed: u64) {
+        let mut rand = Randomizer::new(seed);
+
+        let start = self.pos(x, y);
+        let neighbours = self.neighbours();
+        let mut mines = self.roll_mines(&mut rand, start, &neighbours);
+
+        if self.no_guess {
+            let mut rolls = 1;
+            let mut repairs = 0;
+            loop {
+                let (mine, targets) = {
+                    let mut solver = Solver::new(&neighbours, &mines);
+                    if solver.solve(start) {
+                        break;
+                    }
+
+                    // Possible new positions for a blocking mine: free cells away from the
+                    // revealed area.
+                    let targets: Vec<usize> = (0..mines.len()).filter(|&c| {
+                        !mines[c] && c != start && !neighbours[start].contains(&c)
+                            && !solver.is_revealed(c)
+                            && !neighbours[c].iter().any(|&n| solver.is_revealed(n))
+                    }).collect();
+
+                    (solver.stuck_mine(), targets)
+                };
+
+                if rolls < MAX_REROLLS {
+                    mines = self.roll_mines(&mut rand, start, &neighbours);
+                    rolls += 1;
+                } else if let Some(mine) = mine {
+                    mines[mine] = false;
+                    // Moving mines around could go on forever, so eventually we just remove
+                    // them.
+                    if !targets.is_empty() && repairs < mines.len() {
+                        let target = targets[rand_u64(&mut rand) as usize % targets.len()];
+                        mines[target] = true;
+                        repairs += 1;
+                    }
+                } else {
+                    // Nothing blocks the solver, yet it is stuck. This cannot happen, but
+                    // there is no reason to hang if it does.
+                    break;
+                }
+            }
+        }
+
+        for (cell, &mine) in self.grid.iter_mut().zip(mines.iter()) {
+            cell.mine = mine;
+        }
+
+        self.code = Some(BoardCode {
+            width: self.width,
+            height: self.height(),
+            mines: self.mines,
+            topology: self.topology,
+            no_guess: self.no_guess,
+            x: x,
+            y: y,
+            seed: seed,
+        });
+    }
+
+    /// Click the cell at (x, y).
+    ///
+    /// Concealed cells are revealed, and revealed cells are chorded. Hitting a mine loses the
+    /// game. Returns the cells which changed.
+    pub fn click(&mut self, x: u16, y: u16) -> Vec<(u16, u16)> {
+        let mut changed = Vec::new();
+        let cell = self.cell(x, y);
+
+        if cell.revealed {
+            return self.chord(x, y);
+        }
+
+        if cell.mine {
+            self.exploded = Some((x, y));
+        } else {
+            self.points += 1;
+            self.reveal(x, y, &mut changed);
+        }
+
+        changed
+    }
+
+    /// Chord the cell at (x, y).
+    ///
+    /// If the cell is revealed, and the number of flags around it equals its value, all the
+    /// adjacent cells which are neither flagged nor revealed get revealed. Question marks are
+    /// revealed like unmarked cells. Hitting a mine
+    /// (because of a wrong flag) loses the game. Returns the cells which changed.
+    pub fn chord(&mut self, x: u16, y: u16) -> Vec<(u16, u16)> {
+        let mut changed = Vec::new();
+        if !self.cell(x, y).revealed {
+            return changed;
+        }
+
+        let adjacent = self.adjacent(x, y);
+        let flags = adjacent.iter().filter(|&&(x, y)| self.cell(x, y).flagged).count();
+        if flags != self.value(x, y) as usize {
+            return changed;
+        }
+
+        for &(x, y) in adjacent.iter() {
+            let cell = self.cell(x, y);
+            if cell.flagged || cell.revealed {
+                continue;
+            }
+
+            if cell.mine {
+                self.exploded = Some((x, y));
+                break;
+            }
+
+            self.points += 1;
+            self.reveal(x, y, &mut changed);
+        }
+
+        changed
+    }
+
+    /// Reveal the cell at (x, y), pushing the cells revealed to _changed_.
+    ///
+    /// Free cells reveal their neighbours in turn, until non-free cells are reached.
+    fn reveal(&mut self, x: u16, y: u16, changed: &mut Vec<(u16, u16)>) {
+        let mut stack = vec![(x, y)];
+
+        while let Some((x, y)) = stack.pop() {
+            let pos = self.pos(x, y);
+            if self.grid[pos].revealed || self.grid[pos].mine {
+                continue;
+            }
+
+            self.grid[pos].revealed = true;
+            // A revealed cell cannot keep its mark.
+            self.grid[pos].flagged = false;
+            self.grid[pos].questioned = false;
+            changed.push((x, y));
+
+            if self.value(x, y) == 0 {
+                let adjacent = self.adjacent(x, y);
+                stack.extend(adjacent.into_iter().filter(|&(x, y)| !self.cell(x, y).revealed));
+            }
+        }
+    }
+
+    /// Cycle the mark of the cell at (x, y): a flag, then a question mark, then nothing.
+    ///
+    /// Revealed cells cannot be marked. Returns the cells which changed.
+    pub fn cycle_mark(&mut self, x: u16, y: u16) -> Vec<(u16, u16)> {
+        let pos = self.pos(x, y);
+        let cell = &mut self.grid[pos];
+        if cell.revealed {
+            return Vec::new();
+        }
+
+        let (flagged, questioned) = match (cell.flagged, cell.questioned) {
+            (false, false) => (true, false),
+            (true, _) => (false, true),
+            (false, true) => (false, false),
+        };
+        cell.flagged = flagged;
+        cell.questioned = questioned;
+        vec![(x, y)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Build a started board from a picture of its rows, where `*` is a mine.
+    fn board(topology: Topology, rows: &[&str]) -> Board {
+        let (width, height) = (rows[0].len() as u16, rows.len() as u16);
+        let grid: Vec<Cell> = rows.iter().flat_map(|row| row.chars()).map(|c| Cell {
+            mine: c == '*',
+            ..Cell::new()
+        }).collect();
+        let mines = grid.iter().filter(|cell| cell.mine).count() as u32;
+
+        let mut board = Board::new(width, height, mines, topology, false);
+        board.restore(grid, 0, Some(BoardCode {
+            width: width,
+            height: height,
+            mines: mines,
+            topology: topology,
+            no_guess: false,
+            x: 0,
+            y: 0,
+            seed: 0,
+        }));
+        board
+    }
+
+    /// Sort a list of changed cells, which comes in no particular order.
+    fn sorted(mut cells: Vec<(u16, u16)>) -> Vec<(u16, u16)> {
+        cells.sort();
+        cells
+    }
+
+    #[test]
+    fn flood_fill_stops_at_numbers() {
+        let rows = ["..*..", "..*..", "..*.."];
+
+        let mut square = board(Topology::Square, &rows);
+        assert_eq!(sorted(square.click(0, 0)),
+                   vec![(0, 0), (0, 1), (0, 2), (1, 0), (1, 1), (1, 2)]);
+        assert_eq!(square.value(1, 1), 3);
+        assert!(!square.cell(3, 0).revealed);
+        assert_eq!(square.state(), State::Playing);
+
+        // On a torus, the zero region goes on over the left edge.
+        let mut torus = board(Topology::Torus, &rows);
+        assert_eq!(sorted(torus.click(0, 0)),
+                   vec![(0, 0), (0, 1), (0, 2), (1, 0), (1, 1), (1, 2),
+                        (3, 0), (3, 1), (3, 2), (4, 0), (4, 1), (4, 2)]);
+        assert_eq!(torus.state(), State::Won);
+    }
+
+    #[test]
+    fn first_click_is_safe_everywhere() {
+        let (width, height) = (9, 8);
+        let starts = [(0, 0), (width - 1, 0), (0, height - 1), (width - 1, height - 1),
+                      (4, 0), (0, 4), (width - 1, 3), (5, height - 1), (4, 4)];
+
+        for &topology in [Topology::Square, Topology::Torus, Topology::Hex].iter() {
+            for &(x, y) in starts.iter() {
+                for seed in 0..10 {
+                    let mut board = Board::new(width, height, 50, topology, false);
+                    board.place_mines(x, y, seed);
+                    assert_eq!(board.count_mines(), 50);
+
+                    let changed = board.click(x, y);
+                    assert!(changed.contains(&(x, y)), "{:?} at {:?}", topology, (x, y));
+                    assert_eq!(board.value(x, y), 0);
+                    assert!(board.state() != State::Lost(x, y));
+                }
+            }
+        }
+    }
+
+    /// Check that the solver clears a board from (x, y), which keeps its neighbours free.
+    fn assert_solvable(board: &Board, x: u16, y: u16) {
+        let mines: Vec<bool> = board.cells().iter().map(|cell| cell.mine).collect();
+        let neighbours = board.neighbours();
+        let start = board.pos(x, y);
+
+        assert!(!mines[start] && neighbours[start].iter().all(|&n| !mines[n]));
+        assert!(Solver::new(&neighbours, &mines).solve(start), "{:?}", board.code());
+    }
+
+    #[test]
+    fn no_guess_boards_are_solvable() {
+        for seed in 0..20 {
+            let mut board = Board::new(16, 12, 40, Topology::Square, true);
+            board.place_mines(3, 4, seed);
+            assert_eq!(board.count_mines(), 40);
+            assert_solvable(&board, 3, 4);
+        }
+    }
+
+    #[test]
+    fn dense_no_guess_boards_are_repaired() {
+        // Hardly any board this dense is solvable, so the rerolls run out and the last board is
+        // repaired, which drops the mines there is no room to move.
+        let mut repaired = 0;
+        for seed in 0..10 {
+            let mut board = Board::new(6, 6, 20, Topology::Square, true);
+            board.place_mines(0, 0, seed);
+            assert!(board.count_mines() <= 20);
+            if board.count_mines() < 20 {
+                repaired += 1;
+            }
+            assert_solvable(&board, 0, 0);
+        }
+
+        assert!(repaired > 0);
+    }
+
+    #[test]
+    fn reveal_flag_and_chord_on_the_border() {
+        let mut board = board(Topology::Square, &["*.*.", "....", "..*."]);
+
+        // A number reveals nothing but itself.
+        assert_eq!(board.click(0, 1), vec![(0, 1)]);
+        assert_eq!(board.value(0, 1), 1);
+
+        // Without its flag, the cell does not chord.
+        assert_eq!(board.chord(0, 1), vec![]);
+
+        assert_eq!(board.cycle_mark(0, 0), vec![(0, 0)]);
+        assert!(board.cell(0, 0).flagged);
+        assert_eq!(board.cycle_mark(0, 1), vec![]);
+
+        // The zero in the corner reveals the cells around it too, but each is listed once.
+        assert_eq!(sorted(board.chord(0, 1)), vec![(0, 2), (1, 0), (1, 1), (1, 2)]);
+        assert_eq!(board.value(1, 0), 2);
+        assert_eq!(board.value(0, 2), 0);
+        assert_eq!(board.state(), State::Playing);
+
+        // A wrong flag in the other corner makes the chord hit a mine, after revealing what it
+        // got to first.
+        assert_eq!(board.click(3, 2), vec![(3, 2)]);
+        assert_eq!(board.cycle_mark(3, 1), vec![(3, 1)]);
+        assert_eq!(board.chord(3, 2), vec![(2, 1)]);
+        assert_eq!(board.state(), State::Lost(2, 2));
+    }
+}
@@ -0,0 +1,168 @@
+//! Board codes.
+//!
+//! A board code holds everything the mine placement depends on, so that a board can be shared
+//! and played again. It looks like `t30x16-80-12.5-9f3a1c2e04b7d615`, that is:
+//!
+//! ```text
+//! <topology><width>x<height>-<mines>-<x>.<y>-<seed>[-ng]
+//! ```
+//!
+//! The topology is given by its first letter, the first click by (x, y), and the seed in
+//! hexadecimal. Boards generated in no-guess mode end with `-ng`.
+
+use std::fmt;
+
+use topology::Topology;
+
+/// The parameters of a generated board.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct BoardCode {
+    /// Width of the grid.
+    pub width: u16,
+    /// Height of the grid.
+    pub height: u16,
+    /// The number of mines.
+    pub mines: u32,
+    /// The shape of the board.
+    pub topology: Topology,
+    /// Was the board generated in no-guess mode?
+    pub no_guess: bool,
+    /// The x coordinate of the first click.
+    pub x: u16,
+    /// The y coordinate of the first click.
+    pub y: u16,
+    /// The seed of the mine placement.
+    pub seed: u64,
+}
+
+impl BoardCode {
+    /// Parse a board code.
+    pub fn parse(code: &str) -> Option<BoardCode> {
+        let mut parts = code.split('-');
+
+        let size = parts.next()?;
+        let topology = match size.chars().next()? {
+            's' => Topology::Square,
+            't' => Topology::Torus,
+            'h' => Topology::Hex,
+            _ => return None,
+        };
+        let mut size = size[1..].split('x');
+        let width = size.next()?.parse().ok()?;
+        let height = size.next()?.parse().ok()?;
+
+        let mines = parts.next()?.parse().ok()?;
+
+        let mut start = parts.next()?.split('.');
+        let x = start.next()?.parse().ok()?;
+        let y = start.next()?.parse().ok()?;
+
+        let seed = u64::from_str_radix(parts.next()?, 16).ok()?;
+
+        let no_guess = match parts.next() {
+            None => false,
+            Some("ng") => true,
+            Some(_) => return None,
+        };
+
+        let code = BoardCode {
+            width: width,
+            height: height,
+            mines: mines,
+            topology: topology,
+            no_guess: no_guess,
+            x: x,
+            y: y,
+            seed: seed,
+        };
+
+        if size.next().is_some() || start.next().is_some() || parts.next().is_some()
+            || width == 0 || height == 0 || x >= width || y >= height
+            || mines as u64 >= width as u64 * height as u64 {
+            None
+        } else {
+            Some(code)
+        }
+    }
+}
+
+impl fmt::Display for BoardCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}x{}-{}-{}.{}-{:x}", &self.topology.name()[..1], self.width, self.height,
+               self.mines, self.x, self.y, self.seed)?;
+
+        if self.no_guess {
+            write!(f, "-ng")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use board::Board;
+
+    /// Place the mines of a board from scratch.
+    fn mines(width: u16, height: u16, mines: u32, topology: Topology, no_guess: bool,
+             (x, y): (u16, u16), seed: u64) -> Vec<bool> {
+        let mut board = Board::new(width, height, mines, topology, no_guess);
+        board.place_mines(x, y, seed);
+        board.cells().iter().map(|cell| cell.mine).collect()
+    }
+
+    #[test]
+    fn same_seed_same_layout() {
+        for &topology in [Topology::Square, Topology::Torus, Topology::Hex].iter() {
+            for seed in 0..20 {
+                let first = mines(16, 12, 40, topology, false, (3, 4), seed);
+                assert_eq!(first, mines(16, 12, 40, topology, false, (3, 4), seed));
+                assert_eq!(first.iter().filter(|&&mine| mine).count(), 40);
+            }
+        }
+
+        assert_eq!(mines(9, 9, 10, Topology::Square, true, (4, 4), 7),
+                   mines(9, 9, 10, Topology::Square, true, (4, 4), 7));
+        assert!(mines(16, 12, 40, Topology::Square, false, (3, 4), 1)
+                != mines(16, 12, 40, Topology::Square, false, (3, 4), 2));
+    }
+
+    #[test]
+    fn printed_code_gives_the_same_board() {
+        let mut board = Board::new(30, 16, 80, Topology::Torus, true);
+        board.place_mines(12, 5, 0x9f3a1c2e04b7d615);
+        let code = board.code().unwrap();
+
+        let printed = code.to_string();
+        assert_eq!(printed, "t30x16-80-12.5-9f3a1c2e04b7d615-ng");
+
+        let parsed = BoardCode::parse(&printed).unwrap();
+        assert_eq!(parsed, code);
+
+        let mut again = Board::new(parsed.width, parsed.height, parsed.mines, parsed.topology,
+                                   parsed.no_guess);
+        again.place_mines(parsed.x, parsed.y, parsed.seed);
+        assert_eq!(again.cells(), board.cells());
+        assert_eq!(again.code(), Some(code));
+    }
+
+    #[test]
+    fn invalid_codes() {
+        assert_eq!(BoardCode::parse("s9x9-10-4.4-ff"), Some(BoardCode {
+            width: 9,
+            height: 9,
+            mines: 10,
+            topology: Topology::Square,
+            no_guess: false,
+            x: 4,
+            y: 4,
+            seed: 0xff,
+        }));
+        assert_eq!(BoardCode::parse("x9x9-10-4.4-ff"), None);
+        assert_eq!(BoardCode::parse("s9x9-10-9.4-ff"), None);
+        assert_eq!(BoardCode::parse("s9x9-81-4.4-ff"), None);
+        assert_eq!(BoardCode::parse("s9x9-10-4.4-ff-xx"), None);
+        assert_eq!(BoardCode::parse("s9x9-10-4.4"), None);
+    }
+}
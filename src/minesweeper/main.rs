@@ -4,36 +4,66 @@
 extern crate termion;
 extern crate extra;
 
+mod board;
+mod code;
+mod replay;
+mod save;
+mod scores;
+mod solver;
+mod topology;
+
 use termion::{clear, cursor, color, style};
 use termion::raw::IntoRawMode;
-use termion::input::TermRead;
-use termion::event::Key;
+use termion::input::{MouseTerminal, TermRead};
+use termion::event::{Event, Key, MouseButton, MouseEvent};
 
+use std::cmp;
 use std::env;
-use std::io::{self, Read, Write};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
 use std::process;
+use std::thread;
+use std::time::{Duration, Instant};
 
 use extra::rand::Randomizer;
 
-/// A cell in the grid.
-#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
-struct Cell {
-    /// Does it contain a mine?
-    mine: bool,
-    /// Is it revealed?
-    ///
-    /// That is, is it showed or chosen previously by the player?
-    revealed: bool,
-    /// Is this cell observed?
-    ///
-    /// That is, is the state of this cell determined, or is it pending for randomization.
-    observed: bool,
-    /// Does this flag contain a flag?
-    flagged: bool,
+use board::{Board, Cell, State};
+use code::BoardCode;
+use replay::{Input, Replay};
+use save::Save;
+use scores::{Score, Scores};
+use solver::View;
+use topology::Topology;
+
+/// An action of the player, as remembered for undoing it.
+///
+/// The placement of the mines is not part of any action, so undoing the first click keeps the
+/// board.
+struct Action {
+    /// The cells changed by the action, by position, with their state from before it.
+    cells: Vec<(usize, Cell)>,
+    /// The points from before the action.
+    points: u16,
 }
 
+/// The file games are saved to, unless another one was loaded.
+const SAVE_FILE: &'static str = "minesweeper.save";
+/// The file the last game is recorded to.
+const REPLAY_FILE: &'static str = "minesweeper.replay";
+
+/// The time between two frames of a replay, in milliseconds.
+const REPLAY_FRAME: u64 = 20;
+/// The time skipped by seeking in a replay, in milliseconds.
+const REPLAY_SEEK: u64 = 5000;
+/// The fastest a replay can be played.
+const MAX_SPEED: f64 = 64.0;
+/// The slowest a replay can be played.
+const MIN_SPEED: f64 = 1.0 / 8.0;
+
 /// The string printed for flagged cells.
 const FLAGGED: &'static str = "F";
+/// The string printed for cells marked with a question mark.
+const QUESTIONED: &'static str = "?";
 /// The string printed for mines in the game over revealing.
 const MINE: &'static str = "*";
 /// The string printed for concealed cells.
@@ -46,6 +76,13 @@ const GAME_OVER: &'static str = "╔══════════════
                                  ║ q ┆ quit        ║\n\r\
                                  ╚═══╧═════════════╝";
 
+/// The winning screen.
+const WON: &'static str = "╔═════════════════╗\n\r\
+                           ║───┬You won!─────║\n\r\
+                           ║ r ┆ replay      ║\n\r\
+                           ║ q ┆ quit        ║\n\r\
+                           ╚═══╧═════════════╝";
+
 /// The upper and lower boundary char.
 const HORZ_BOUNDARY: &'static str = "─";
 /// The left and right boundary char.
@@ -67,31 +104,65 @@ minesweeper ~ a simple minesweeper implementation.
 rules:
     Select a cell to reveal, printing the number of adjacent cells holding a mine.
     If no adjacent cells hold a mine, the cell is called free. Free cell will recursively
-    reveal their neighboring cells. If a mine is revealed, you loose. The grid wraps.
+    reveal their neighboring cells. If a mine is revealed, you loose. Once every cell
+    without a mine is revealed, you win.
+    Chording a revealed cell, whose number of adjacent flags matches its number, reveals
+    the rest of its neighboring cells.
 
 flags:
     -r | --height N ~ set the height of the grid.
     -c | --width N  ~ set the width of the grid.
     -h | --help     ~ this help page.
-    -b              ~ beginner mode.
-    -i              ~ intermediate mode.
-    -a              ~ advanced mode.
-    -g              ~ god mode.
+    -b              ~ beginner mode (1 mine in 10 cells).
+    -i              ~ intermediate mode (1 mine in 6 cells).
+    -a              ~ advanced mode (1 mine in 4 cells).
+    -g              ~ god mode (1 mine in 2 cells).
+    --mines N       ~ place exactly N mines.
+    --density P     ~ fill P percent of the cells with mines.
+    --no-guess      ~ only generate boards which can be solved without guessing.
+    --seed N        ~ place the mines deterministically from the seed N.
+    --board CODE    ~ play the board of the given code, as shown at the end of a game.
+    --practice      ~ practice mode, where actions (even fatal ones) can be undone.
+    --load FILE     ~ resume the game saved in FILE (and save to it again).
+    --scores        ~ show the high scores.
+    --replay FILE   ~ watch a recorded game. The last game is always recorded to
+                      minesweeper.replay.
+    --speed X       ~ watch replays X times faster (or slower, if X is below 1).
+    --topology T    ~ set the shape of the board:
+                      square ~ the classic, bounded grid.
+                      torus  ~ a grid wrapping around its edges (the default).
+                      hex    ~ a grid of hexagons, with six neighbors each.
 
 controls:
     ---selection--------------------
-    space ~ reveal the current cell.
+    space ~ reveal the current cell (or chord, if it is revealed).
+    c     ~ chord the current cell.
     ---movement---------------------
     h | a ~ move left.
     j | s ~ move down.
     k | w ~ move up.
     l | d ~ move right.
     ---flags------------------------
-    f     ~ set flag.
-    F     ~ remove flag.
+    f     ~ cycle the mark: flag, question mark, none.
     ---control----------------------
     q     ~ quit game.
     r     ~ restart game.
+    x     ~ save and quit game.
+    u     ~ undo the last action (in practice mode).
+    o     ~ settings: size, mines and topology (before the first click).
+    ?     ~ hint: point out the cell least likely to hold a mine.
+    ---mouse------------------------
+    left          ~ reveal (or chord) the cell, once released.
+    right         ~ cycle the mark.
+    middle        ~ chord the cell.
+    left + right  ~ chord the cell.
+    ---replays---------------------
+    space ~ pause or resume.
+    n     ~ play the next input, and pause.
+    h | l ~ seek five seconds backwards or forwards.
+    0     ~ seek back to the start.
+    + | - ~ play faster or slower.
+    q     ~ quit.
 
 author:
     ticki.
@@ -99,27 +170,46 @@ author:
 
 /// The game state.
 struct Game<R, W: Write> {
-    /// Width of the grid.
-    width: u16,
-    /// The grid.
-    ///
-    /// The cells are enumerated like you would read a book. Left to right, until you reach the
-    /// line ending.
-    grid: Box<[Cell]>,
-    /// The difficulty of the game.
-    ///
-    /// The lower, the easier.
-    difficulty: u8,
+    /// The board.
+    board: Board,
     /// The x coordinate.
     x: u16,
     /// The y coordinate.
     y: u16,
     /// The randomizer.
     rand: Randomizer,
-    /// Points.
+    /// The seed to place the mines from.
     ///
-    /// That is, revealed fields.
-    points: u16,
+    /// If not set, a new seed is drawn from the randomizer for every board.
+    seed: Option<u64>,
+    /// Can actions be undone?
+    practice: bool,
+    /// The actions so far, latest last.
+    ///
+    /// This is only kept in practice mode.
+    history: Vec<Action>,
+    /// The number of actions undone.
+    undos: u16,
+    /// The number of hints asked for.
+    hints: u16,
+    /// The cell highlighted by the last hint, if it still is.
+    hinted: Option<(u16, u16)>,
+    /// The file to save the game to.
+    save_file: String,
+    /// The time at which the game started.
+    ///
+    /// That is, the time of the first click.
+    timer: Option<Instant>,
+    /// The game as it was when the recording started.
+    replay_start: Option<Save>,
+    /// The time at which the recording started.
+    recording: Instant,
+    /// The inputs recorded so far, with the time they were made at.
+    inputs: Vec<(u64, Input)>,
+    /// Is the left mouse button held down?
+    left_held: bool,
+    /// Is the right mouse button held down?
+    right_held: bool,
     /// Standard output.
     stdout: W,
     /// Standard input.
@@ -127,32 +217,91 @@ struct Game<R, W: Write> {
 }
 
 /// Initialize the game.
-fn init<W: Write, R: Read>(mut stdout: W, stdin: R, difficulty: u8, w: u16, h: u16) {
+///
+/// If _start_ is set, the first click is made on that cell right away.
+fn init<W: Write, R: Read>(mut stdout: W, stdin: R, mines: u32, topology: Topology, no_guess: bool,
+                           w: u16, h: u16, save_file: String, save: Option<Save>, seed: Option<u64>,
+                           start: Option<(u16, u16)>, practice: bool) {
     write!(stdout, "{}", clear::All).unwrap();
 
     // Set the initial game state.
     let mut game = Game {
+        board: Board::new(w, h, mines, topology, no_guess),
         x: 0,
         y: 0,
         rand: Randomizer::new(0),
-        width: w,
-        grid: vec![Cell {
-            mine: false,
-            revealed: false,
-            observed: false,
-            flagged: false,
-        }; w as usize * h as usize].into_boxed_slice(),
-        points: 0,
-        stdin: stdin.keys(),
+        seed: seed,
+        practice: practice,
+        history: Vec::new(),
+        undos: 0,
+        hints: 0,
+        hinted: None,
+        save_file: save_file,
+        timer: None,
+        replay_start: None,
+        recording: Instant::now(),
+        inputs: Vec::new(),
+        left_held: false,
+        right_held: false,
+        stdin: stdin.events(),
         stdout: stdout,
-        difficulty: difficulty,
     };
 
     // Reset that game.
     game.reset();
 
+    // Pick up where the player left off.
+    if let Some(save) = save {
+        game.restore(save);
+    }
+
+    // Replay the first click of a shared board.
+    if let Some((x, y)) = start {
+        game.perform(Input::Click(x, y));
+        write!(game.stdout, "{}", game.goto(x, y)).unwrap();
+        game.stdout.flush().unwrap();
+    }
+
     // Start the event loop.
     game.start();
+    game.write_replay();
+}
+
+/// Watch a replay.
+///
+/// The inputs are played back on the starting position of the replay, _speed_ times faster than
+/// they were made.
+fn watch<W: Write, R: Read>(mut stdout: W, stdin: R, replay: Replay, speed: f64) {
+    write!(stdout, "{}", clear::All).unwrap();
+
+    let board = {
+        let start = &replay.start;
+        let no_guess = start.board.map_or(false, |code| code.no_guess);
+        Board::new(start.width, start.height, start.mines, start.topology, no_guess)
+    };
+    let mut game = Game {
+        board: board,
+        x: 0,
+        y: 0,
+        rand: Randomizer::new(0),
+        seed: None,
+        practice: replay.practice,
+        history: Vec::new(),
+        undos: 0,
+        hints: 0,
+        hinted: None,
+        save_file: String::new(),
+        timer: None,
+        replay_start: None,
+        recording: Instant::now(),
+        inputs: Vec::new(),
+        left_held: false,
+        right_held: false,
+        stdin: stdin.events(),
+        stdout: stdout,
+    };
+
+    game.playback(&replay, speed);
 }
 
 impl<R, W: Write> Drop for Game<R, W> {
@@ -162,151 +311,699 @@ impl<R, W: Write> Drop for Game<R, W> {
     }
 }
 
-impl<R: Iterator<Item=Result<Key, std::io::Error>>, W: Write> Game<R, W> {
-    /// Get the grid position of a given coordinate.
-    fn pos(&self, x: u16, y: u16) -> usize {
-        y as usize * self.width as usize + x as usize
-    }
-
-    /// Read cell, randomizing it if it is unobserved.
-    fn read_cell(&mut self, c: usize) {
-        if !self.grid[c].observed {
-            self.grid[c].mine = self.rand.read_u8() % self.difficulty == 0;
-            self.grid[c].observed = true;
-        }
-    }
-
-    /// Get the cell at (x, y).
-    fn get(&mut self, x: u16, y: u16) -> Cell {
-        let pos = self.pos(x, y);
-
-        self.read_cell(pos);
-        self.grid[pos]
-    }
-
-    /// Get a mutable reference to the cell at (x, y).
-    fn get_mut(&mut self, x: u16, y: u16) -> &mut Cell {
-        let pos = self.pos(x, y);
-
-        self.read_cell(pos);
-        &mut self.grid[pos]
+impl<R: Iterator<Item=Result<Event, std::io::Error>>, W: Write> Game<R, W> {
+    /// Get the cursor movement to the cell at (x, y).
+    fn goto(&self, x: u16, y: u16) -> cursor::Goto {
+        let (col, row) = self.board.topology().screen(x, y);
+        cursor::Goto(col, row)
     }
 
     /// Start the game loop.
     ///
     /// This will listen to events and do the appropriate actions.
     fn start(&mut self) {
-        let mut first_click = true;
         loop {
-            // Read a single byte from stdin.
-            let b = self.stdin.next().unwrap().unwrap();
+            // Read a single event from stdin.
+            let event = self.stdin.next().unwrap().unwrap();
+            let b = match event {
+                Event::Key(b) => b,
+                Event::Mouse(mouse) => {
+                    if !self.mouse(mouse) && !self.game_over() {
+                        return;
+                    }
+                    Key::Null
+                },
+                Event::Unsupported(_) => Key::Null,
+            };
+
             use termion::event::Key::*;
             if let Char(c) = b {
                 // Collect it as entropy.
                 self.rand.write_u8(c as u8);
             }
+            let (x, y) = (self.x, self.y);
             match b {
-                Char('h') | Char('a') | Left  => self.x = self.left(self.x),
-                Char('j') | Char('s') | Down  => self.y = self.down(self.y),
-                Char('k') | Char('w') | Up    => self.y = self.up(self.y),
-                Char('l') | Char('d') | Right => self.x = self.right(self.x),
-                Char(' ') => {
-                    // Check if it was a mine.
-                    let (x, y) = (self.x, self.y);
-
-                    if first_click {
-                        // This is the player's first turn; clear all cells of
-                        // mines around the cursor.
-                        for &(x, y) in self.adjacent(x, y).iter() {
-                            self.get_mut(x, y).mine = false;
-                        }
-                        self.get_mut(x, y).mine = false;
-                        first_click = false;
-                    }
-
-                    if self.get(x, y).mine {
-                        self.reveal_all();
-                        // Make the background colour of the mine we just
-                        // landed on red, and the foreground black.
-                        write!(self.stdout, "{}{}{}{}{}",
-                               cursor::Goto(x + 2, y + 2),
-                               color::Bg(color::Red), color::Fg(color::Black),
-                               MINE,
-                               style::Reset).unwrap();
-                        self.game_over();
-                        return;
-                    }
-
-                    if !self.get(x, y).revealed {
-                        self.points += 1;
-                    }
-
-                    // Reveal the cell.
-                    self.reveal(x, y);
-
-                    self.print_points();
+                Char('h') | Char('a') | Left  => { self.perform(Input::Cursor(self.left(x), y)); },
+                Char('j') | Char('s') | Down  => { self.perform(Input::Cursor(x, self.down(y))); },
+                Char('k') | Char('w') | Up    => { self.perform(Input::Cursor(x, self.up(y))); },
+                Char('l') | Char('d') | Right => { self.perform(Input::Cursor(self.right(x), y)); },
+                Char(' ') => if !self.perform(Input::Click(x, y)) && !self.game_over() {
+                    return;
+                },
+                Char('c') => if !self.perform(Input::Chord(x, y)) && !self.game_over() {
+                    return;
                 },
                 Char('f') => {
-                    let (x, y) = (self.x, self.y);
-                    self.toggle_flag(x, y);
-                }
+                    self.perform(Input::Mark(x, y));
+                },
+                Char('u') if self.practice => {
+                    self.perform(Input::Undo);
+                },
+                Char('?') => {
+                    self.perform(Input::Hint);
+                },
+                Char('o') if !self.board.started() => self.settings(),
                 Char('r') => {
                     self.restart();
                     return;
                 }
                 Char('q') => return,
+                Char('x') => match self.save() {
+                    Ok(()) => return,
+                    Err(err) => {
+                        // Tell the player below the grid, and let them play on.
+                        let height = self.board.height();
+                        write!(self.stdout, "{}{}could not save game: {}",
+                               cursor::Goto(1, height + 3), clear::CurrentLine, err).unwrap();
+                    },
+                },
                 _ => {},
             }
 
+            if self.board.state() == State::Won {
+                self.win();
+                return;
+            }
+
             // Make sure the cursor is placed on the current position.
-            write!(self.stdout, "{}", cursor::Goto(self.x + 2, self.y + 2)).unwrap();
+            write!(self.stdout, "{}", self.goto(self.x, self.y)).unwrap();
             self.stdout.flush().unwrap();
         }
     }
 
-    /// Set a flag on cell.
-    fn set_flag(&mut self, x: u16, y: u16) {
-        if !self.get(x, y).revealed {
-            self.stdout.write(FLAGGED.as_bytes()).unwrap();
-            self.get_mut(x, y).flagged = true;
+    /// Handle a mouse event.
+    ///
+    /// Pressing the left button moves the cursor, and releasing it clicks the cell it was
+    /// released on, unless the right button was pressed in between, which makes it a chord. The
+    /// right button alone marks a cell as soon as it is pressed, and the middle one chords it.
+    /// Clicks on the frame or outside the grid are ignored. Returns false if a mine was hit.
+    fn mouse(&mut self, event: MouseEvent) -> bool {
+        let (col, row) = match event {
+            MouseEvent::Press(_, col, row) | MouseEvent::Hold(col, row) => (col, row),
+            MouseEvent::Release(col, row) => (col, row),
+        };
+
+        // The terminal coordinates are 1-based just like `Goto`, so the topology can map them
+        // back to a cell.
+        let (width, height) = (self.board.width(), self.board.height());
+        let cell = self.board.topology().cell_at(width, height, col, row);
+
+        if let MouseEvent::Release(..) = event {
+            // The terminal does not tell which button went up, so any release ends the click.
+            let (left, chord) = (self.left_held, self.left_held && self.right_held);
+            self.left_held = false;
+            self.right_held = false;
+
+            return match cell {
+                Some((x, y)) if chord => self.perform(Input::Chord(x, y)),
+                Some((x, y)) if left => self.perform(Input::Click(x, y)),
+                _ => true,
+            };
+        }
+
+        let (x, y) = match cell {
+            Some(cell) => cell,
+            None => return true,
+        };
+
+        // Collect the position as entropy.
+        self.rand.write_u8(x as u8);
+        self.rand.write_u8(y as u8);
+
+        let button = match event {
+            MouseEvent::Press(button, _, _) => button,
+            _ => return true,
+        };
+
+        match button {
+            MouseButton::Left => {
+                self.left_held = true;
+                self.perform(Input::Cursor(x, y))
+            },
+            MouseButton::Right if self.left_held => {
+                self.right_held = true;
+                self.perform(Input::Cursor(x, y))
+            },
+            MouseButton::Right => {
+                self.right_held = true;
+                self.perform(Input::Mark(x, y))
+            },
+            MouseButton::Middle => self.perform(Input::Chord(x, y)),
+            _ => true,
+        }
+    }
+
+    /// Perform an input of the player, and record it.
+    ///
+    /// Every input goes through here, both while playing and while watching a replay, so that
+    /// replays are played exactly like the game was. Returns false if a mine was hit.
+    fn perform(&mut self, input: Input) -> bool {
+        let time = millis(self.recording.elapsed());
+        self.inputs.push((time, input));
+
+        // The highlight of a hint only lasts until the next input.
+        if let Some((x, y)) = self.hinted.take() {
+            self.draw_cell(x, y);
+        }
+
+        match input {
+            Input::Cursor(x, y) => {
+                self.x = x;
+                self.y = y;
+                true
+            },
+            Input::Click(x, y) => {
+                self.x = x;
+                self.y = y;
+                self.place_mines();
+                self.record(|game| game.click())
+            },
+            Input::Chord(x, y) => {
+                self.x = x;
+                self.y = y;
+                self.record(|game| game.chord(x, y))
+            },
+            Input::Mark(x, y) => {
+                self.x = x;
+                self.y = y;
+                self.record(|game| game.cycle_mark(x, y))
+            },
+            Input::Undo => {
+                self.undo();
+                true
+            },
+            Input::Hint => {
+                self.hint();
+                true
+            },
         }
     }
-    /// Remove a flag on cell.
-    fn remove_flag(&mut self, x: u16, y: u16) {
-        self.stdout.write(CONCEALED.as_bytes()).unwrap();
-        self.get_mut(x, y).flagged = false;
+
+    /// Perform an action, remembering what it changed in practice mode.
+    ///
+    /// The action returns false if it hit a mine, and so does this. Such an action is
+    /// remembered even though it changed nothing, so that it can be undone from the game over
+    /// screen.
+    fn record<F: FnOnce(&mut Self) -> bool>(&mut self, action: F) -> bool {
+        if !self.practice {
+            return action(self);
+        }
+
+        let before = self.board.cells().to_vec();
+        let points = self.board.points();
+
+        let alive = action(self);
+
+        let cells: Vec<(usize, Cell)> = before.into_iter().enumerate()
+            .filter(|&(c, cell)| self.board.cells()[c] != cell)
+            .collect();
+        if !cells.is_empty() || !alive {
+            self.history.push(Action {
+                cells: cells,
+                points: points,
+            });
+        }
+
+        alive
     }
-    /// Place a flag on cell if unflagged, or remove it if present.
-    fn toggle_flag(&mut self, x: u16, y: u16) {
-        if !self.get(x, y).flagged {
-            self.set_flag(x, y);
+
+    /// Undo the last action, redrawing the cells it changed.
+    fn undo(&mut self) {
+        if let Some(action) = self.history.pop() {
+            for (x, y) in self.board.revert(&action.cells, action.points) {
+                self.draw_cell(x, y);
+            }
+
+            self.undos += 1;
+            self.print_points();
+        }
+    }
+
+    /// Point out the concealed cell least likely to hold a mine, and move the cursor to it.
+    ///
+    /// The cell is highlighted in green if it is provably safe, and in yellow otherwise, with the
+    /// odds printed below the grid.
+    fn hint(&mut self) {
+        let height = self.board.height();
+        write!(self.stdout, "{}{}", cursor::Goto(1, height + 3), clear::CurrentLine).unwrap();
+
+        if !self.board.started() {
+            // The mines are not placed yet, and will be placed around the first click.
+            self.stdout.write(b"hint: the first click is always safe.").unwrap();
+            return;
+        }
+
+        let mut view = Vec::with_capacity(self.board.cells().len());
+        for y in 0..height {
+            for x in 0..self.board.width() {
+                let cell = self.board.cell(x, y);
+                view.push(if cell.revealed {
+                    View::Revealed(self.board.value(x, y))
+                } else if cell.flagged {
+                    View::Flagged
+                } else {
+                    View::Concealed
+                });
+            }
+        }
+
+        let mines = self.board.count_mines() as usize;
+        let (c, probability) = match solver::hint(&self.board.neighbours(), &view, mines) {
+            Some(hint) => hint,
+            None => return,
+        };
+
+        self.hints += 1;
+        let (x, y) = self.board.coords(c);
+        self.x = x;
+        self.y = y;
+
+        let highlight = if probability == 0.0 {
+            write!(self.stdout, "hint: this cell is safe.").unwrap();
+            color::Bg(color::Green).to_string()
         } else {
-            self.remove_flag(x, y);
+            write!(self.stdout, "hint: this cell holds a mine with a chance of {:.0}%.",
+                   probability * 100.0).unwrap();
+            color::Bg(color::Yellow).to_string()
+        };
+        write!(self.stdout, "{}{}{}{}{}", self.goto(x, y), highlight,
+               color::Fg(color::Black), CONCEALED, style::Reset).unwrap();
+        self.hinted = Some((x, y));
+
+        self.print_points();
+    }
+
+    /// Draw the cell at (x, y), as it currently is.
+    fn draw_cell(&mut self, x: u16, y: u16) {
+        let cell = self.board.cell(x, y);
+        write!(self.stdout, "{}", self.goto(x, y)).unwrap();
+
+        if cell.revealed {
+            match self.board.value(x, y) {
+                0 => self.stdout.write(b" ").unwrap(),
+                v => self.stdout.write(&[b'0' + v]).unwrap(),
+            };
+        } else if cell.flagged {
+            self.stdout.write(FLAGGED.as_bytes()).unwrap();
+        } else if cell.questioned {
+            write!(self.stdout, "{}{}{}", color::Fg(color::Cyan), QUESTIONED, style::Reset).unwrap();
+        } else {
+            self.stdout.write(CONCEALED.as_bytes()).unwrap();
+        }
+    }
+
+    /// Draw the cells changed by an action.
+    fn draw_cells(&mut self, cells: &[(u16, u16)]) {
+        for &(x, y) in cells {
+            self.draw_cell(x, y);
         }
     }
 
+    /// Redraw the whole game, from the state of the board.
+    fn redraw(&mut self) {
+        write!(self.stdout, "{}", clear::All).unwrap();
+        self.draw_frame();
+
+        for y in 0..self.board.height() {
+            for x in 0..self.board.width() {
+                let cell = self.board.cell(x, y);
+                if cell.revealed || cell.flagged || cell.questioned {
+                    self.draw_cell(x, y);
+                }
+            }
+        }
+
+        self.print_points();
+    }
+
+    /// Place the mines, if this is the player's first turn, keeping the cursor and the cells
+    /// around it clear.
+    ///
+    /// This starts the clock, and is done for good: it cannot be undone.
+    fn place_mines(&mut self) {
+        if self.board.started() {
+            return;
+        }
+
+        let seed = match self.seed {
+            Some(seed) => seed,
+            None => rand_u64(&mut self.rand),
+        };
+        self.board.place_mines(self.x, self.y, seed);
+        self.timer = Some(Instant::now());
+    }
+
+    /// Click the cell under the cursor.
+    ///
+    /// Concealed cells are revealed, and revealed cells are chorded. Returns false if this hit a
+    /// mine, ending the game.
+    fn click(&mut self) -> bool {
+        let (x, y) = (self.x, self.y);
+        let changed = self.board.click(x, y);
+        self.act(&changed)
+    }
+
+    /// Chord the cell at (x, y).
+    ///
+    /// Returns false if this hit a mine (because of a wrong flag), ending the game.
+    fn chord(&mut self, x: u16, y: u16) -> bool {
+        let changed = self.board.chord(x, y);
+        self.act(&changed)
+    }
+
+    /// Cycle the mark of a cell: a flag, then a question mark, then nothing.
+    ///
+    /// This always returns true, as marks never blow up.
+    fn cycle_mark(&mut self, x: u16, y: u16) -> bool {
+        let changed = self.board.cycle_mark(x, y);
+        self.act(&changed)
+    }
+
+    /// Draw the outcome of an action, given the cells it changed.
+    ///
+    /// Returns false if the game was lost.
+    fn act(&mut self, changed: &[(u16, u16)]) -> bool {
+        self.draw_cells(changed);
+
+        if let State::Lost(x, y) = self.board.state() {
+            self.explode(x, y);
+            return false;
+        }
+
+        self.print_points();
+        true
+    }
+
+    /// Save the game to the save file.
+    fn save(&mut self) -> io::Result<()> {
+        // The state of the randomizer cannot be read, so we reseed it, and save the new seed.
+        let seed = rand_u64(&mut self.rand);
+        self.rand = Randomizer::new(seed);
+
+        self.snapshot(seed).write(BufWriter::new(File::create(&self.save_file)?))
+    }
+
+    /// Take a snapshot of the game, as saved, with the given seed for the randomizer.
+    fn snapshot(&self, rand: u64) -> Save {
+        Save {
+            width: self.board.width(),
+            height: self.board.height(),
+            mines: self.board.mines(),
+            topology: self.board.topology(),
+            x: self.x,
+            y: self.y,
+            points: self.board.points(),
+            started: self.board.started(),
+            time: self.elapsed(),
+            undos: self.undos,
+            hints: self.hints,
+            board: self.board.code(),
+            rand: rand,
+            grid: self.board.cells().to_vec(),
+        }
+    }
+
+    /// Get the replay of the game so far, if the mines are placed.
+    ///
+    /// The mines are only placed by the first click, so they are filled into the starting
+    /// position now.
+    fn replay(&self) -> Option<Replay> {
+        let mut start = match self.replay_start {
+            Some(ref start) if self.board.started() => start.clone(),
+            _ => return None,
+        };
+
+        for (cell, now) in start.grid.iter_mut().zip(self.board.cells().iter()) {
+            cell.mine = now.mine;
+        }
+        start.started = true;
+        start.board = self.board.code();
+
+        Some(Replay {
+            practice: self.practice,
+            start: start,
+            inputs: self.inputs.clone(),
+        })
+    }
+
+    /// Write the replay of the game to the replay file, if the mines are placed.
+    fn write_replay(&self) {
+        let replay = match self.replay() {
+            Some(replay) => replay,
+            None => return,
+        };
+
+        // Replays are a nicety, so failing to write one should not get in the way of the game.
+        let _ = File::create(REPLAY_FILE).and_then(|file| replay.write(BufWriter::new(file)));
+    }
+
+    /// Play a replay back, until the player quits.
+    ///
+    /// Replays can be paused, played one input at a time, sped up, slowed down, and sought
+    /// through. Seeking backwards plays the replay again from the start, up to the new position.
+    fn playback(&mut self, replay: &Replay, mut speed: f64) {
+        let end = replay.inputs.last().map_or(0, |&(time, _)| time);
+        // The position in the replay, in milliseconds.
+        let mut clock = 0.0;
+        // The next input to play.
+        let mut next = 0;
+        let mut paused = false;
+        let mut last = Instant::now();
+
+        self.rewind(replay);
+
+        loop {
+            // Handle the keys pressed since the last frame, without waiting for more.
+            let mut seek = None;
+            while let Some(event) = self.stdin.next() {
+                match event.unwrap() {
+                    Event::Key(Key::Char(' ')) => paused = !paused,
+                    Event::Key(Key::Char('n')) => {
+                        paused = true;
+                        if let Some(&(time, _)) = replay.inputs.get(next) {
+                            clock = time as f64;
+                        }
+                    },
+                    Event::Key(Key::Char('h')) | Event::Key(Key::Left) => {
+                        seek = Some((clock as u64).saturating_sub(REPLAY_SEEK));
+                    },
+                    Event::Key(Key::Char('l')) | Event::Key(Key::Right) => {
+                        seek = Some(cmp::min(clock as u64 + REPLAY_SEEK, end));
+                    },
+                    Event::Key(Key::Char('0')) => seek = Some(0),
+                    Event::Key(Key::Char('+')) => speed = (speed * 2.0).min(MAX_SPEED),
+                    Event::Key(Key::Char('-')) => speed = (speed / 2.0).max(MIN_SPEED),
+                    Event::Key(Key::Char('q')) => return,
+                    _ => {},
+                }
+            }
+
+            let now = Instant::now();
+            if !paused && next < replay.inputs.len() {
+                clock += millis(now - last) as f64 * speed;
+            }
+            last = now;
+
+            if let Some(time) = seek {
+                if (time as f64) < clock {
+                    self.rewind(replay);
+                    next = 0;
+                }
+                clock = time as f64;
+            }
+
+            // Play the inputs which are due.
+            while next < replay.inputs.len() && replay.inputs[next].0 as f64 <= clock {
+                self.play(replay.inputs[next].1);
+                next += 1;
+            }
+
+            // Show the clock as it was at this point of the game.
+            let played = replay.start.time + clock as u64;
+            self.timer = Instant::now().checked_sub(Duration::from_millis(played));
+            self.print_points();
+
+            let height = self.board.height();
+            write!(self.stdout, "{}{}replay: {} of {} ~ {}x{}", cursor::Goto(1, height + 4),
+                   clear::CurrentLine, scores::format_time(clock as u64), scores::format_time(end),
+                   speed, if paused { " ~ paused" } else { "" }).unwrap();
+            write!(self.stdout, "{}", self.goto(self.x, self.y)).unwrap();
+            self.stdout.flush().unwrap();
+
+            thread::sleep(Duration::from_millis(REPLAY_FRAME));
+        }
+    }
+
+    /// Go back to the starting position of a replay.
+    fn rewind(&mut self, replay: &Replay) {
+        self.reset();
+        self.restore(replay.start.clone());
+    }
+
+    /// Play an input of a replay back.
+    fn play(&mut self, input: Input) {
+        self.perform(input);
+        if input == Input::Undo {
+            // Undoing may take back a fatal action, whose mines are still drawn.
+            self.redraw();
+        }
+    }
+
+    /// Restore a saved game.
+    ///
+    /// This expects the starting grid to be displayed already, and redraws the revealed and
+    /// marked cells on top of it.
+    fn restore(&mut self, save: Save) {
+        self.rand = Randomizer::new(save.rand);
+        self.undos = save.undos;
+        self.hints = save.hints;
+        self.board.restore(save.grid.clone(), save.points, save.board);
+
+        // Record the game from here.
+        self.replay_start = Some(save.clone());
+        self.recording = Instant::now();
+        self.inputs.clear();
+
+        // Keep the clock running from where it stopped.
+        if save.started {
+            let now = Instant::now();
+            self.timer = Some(now.checked_sub(Duration::from_millis(save.time)).unwrap_or(now));
+        }
+
+        self.x = save.x;
+        self.y = save.y;
+        self.redraw();
+        write!(self.stdout, "{}", self.goto(self.x, self.y)).unwrap();
+        self.stdout.flush().unwrap();
+    }
+
     /// Reset the game.
     ///
-    /// This will display the starting grid, and fill the old grid with random mines.
+    /// This will display the starting grid, and clear the board.
     fn reset(&mut self) {
+        self.draw_frame();
+        self.board.clear();
+
+        self.timer = None;
+        self.history.clear();
+        self.undos = 0;
+        self.hints = 0;
+        self.hinted = None;
+        self.print_points();
+
+        // Record the game from here.
+        self.replay_start = Some(self.snapshot(0));
+        self.recording = Instant::now();
+        self.inputs.clear();
+    }
+
+    /// Show the settings screen, where the size, the number of mines and the topology can be
+    /// changed.
+    ///
+    /// Applying new settings starts over on a new board, so this is only offered before the
+    /// first click.
+    fn settings(&mut self) {
+        let topologies = [Topology::Square, Topology::Torus, Topology::Hex];
+        let mut width = self.board.width();
+        let mut height = self.board.height();
+        let mut mines = self.board.mines();
+        let mut topology = self.board.topology();
+        let mut selected = 0;
+
+        loop {
+            // Keep the grid within the terminal, and leave a cell free of mines at least.
+            if let Some((max_width, max_height)) = terminal_grid(topology) {
+                width = cmp::min(width, cmp::max(max_width, 1));
+                height = cmp::min(height, cmp::max(max_height, 1));
+            }
+            mines = cmp::min(mines, (width as u32 * height as u32).saturating_sub(1));
+
+            write!(self.stdout, "{}{}settings:\n\r\n\r", clear::All, cursor::Goto(1, 1)).unwrap();
+            let values = [
+                ("width", width.to_string()),
+                ("height", height.to_string()),
+                ("mines", mines.to_string()),
+                ("topology", topology.name().to_owned()),
+            ];
+            for (i, &(name, ref value)) in values.iter().enumerate() {
+                let marker = if i == selected { '>' } else { ' ' };
+                write!(self.stdout, "  {} {:9} {}\n\r", marker, name, value).unwrap();
+            }
+            write!(self.stdout, "\n\rj/k ~ select, h/l ~ change (H/L by ten), enter ~ apply, \
+                                 esc ~ cancel").unwrap();
+            self.stdout.flush().unwrap();
+
+            let change: i32 = match self.stdin.next().unwrap().unwrap() {
+                Event::Key(Key::Char('k')) | Event::Key(Key::Up) => {
+                    selected = (selected + values.len() - 1) % values.len();
+                    0
+                },
+                Event::Key(Key::Char('j')) | Event::Key(Key::Down) => {
+                    selected = (selected + 1) % values.len();
+                    0
+                },
+                Event::Key(Key::Char('h')) | Event::Key(Key::Left) => -1,
+                Event::Key(Key::Char('l')) | Event::Key(Key::Right) => 1,
+                Event::Key(Key::Char('H')) => -10,
+                Event::Key(Key::Char('L')) => 10,
+                Event::Key(Key::Char('\n')) => {
+                    self.board = Board::new(width, height, mines, topology, self.board.no_guess());
+                    self.x = cmp::min(self.x, width - 1);
+                    self.y = cmp::min(self.y, height - 1);
+
+                    write!(self.stdout, "{}", clear::All).unwrap();
+                    self.reset();
+                    return;
+                },
+                Event::Key(Key::Esc) | Event::Key(Key::Char('q')) => {
+                    self.redraw();
+                    return;
+                },
+                _ => 0,
+            };
+
+            match selected {
+                0 => width = cmp::max(width as i32 + change, 1) as u16,
+                1 => height = cmp::max(height as i32 + change, 1) as u16,
+                2 => mines = cmp::max(mines as i64 + change as i64, 0) as u32,
+                _ => {
+                    let n = topologies.len() as i32;
+                    let i = topologies.iter().position(|&t| t == topology).unwrap_or(0) as i32;
+                    topology = topologies[((i + change.signum() + n) % n) as usize];
+                },
+            }
+        }
+    }
+
+    /// Draw the frame, with all the cells concealed.
+    fn draw_frame(&mut self) {
         // Reset the cursor.
         write!(self.stdout, "{}", cursor::Goto(1, 1)).unwrap();
 
+        let (width, height) = (self.board.width(), self.board.height());
+        let topology = self.board.topology();
+        let columns = topology.columns(width);
+
         // Write the upper part of the frame.
         self.stdout.write(TOP_LEFT_CORNER.as_bytes()).unwrap();
-        for _ in 0..self.width {
+        for _ in 0..columns {
             self.stdout.write(HORZ_BOUNDARY.as_bytes()).unwrap();
         }
         self.stdout.write(TOP_RIGHT_CORNER.as_bytes()).unwrap();
         self.stdout.write(b"\n\r").unwrap();
 
         // Conceal all the cells.
-        for _ in 0..self.height() {
+        for row in 0..height {
             // The left part of the frame
             self.stdout.write(VERT_BOUNDARY.as_bytes()).unwrap();
 
-            for _ in 0..self.width {
-                self.stdout.write_all(CONCEALED.as_bytes()).unwrap();
+            for col in 0..columns {
+                // Depending on the topology, there may be gaps between the cells.
+                if topology.cell_at(width, height, col + 2, row + 2).is_some() {
+                    self.stdout.write_all(CONCEALED.as_bytes()).unwrap();
+                } else {
+                    self.stdout.write(b" ").unwrap();
+                }
             }
 
             // The right part of the frame.
@@ -316,106 +1013,188 @@ impl<R: Iterator<Item=Result<Key, std::io::Error>>, W: Write> Game<R, W> {
 
         // Write the lower part of the frame.
         self.stdout.write(BOTTOM_LEFT_CORNER.as_bytes()).unwrap();
-        for _ in 0..self.width {
+        for _ in 0..columns {
             self.stdout.write(HORZ_BOUNDARY.as_bytes()).unwrap();
         }
         self.stdout.write(BOTTOM_RIGHT_CORNER.as_bytes()).unwrap();
 
-        write!(self.stdout, "{}", cursor::Goto(self.x + 2, self.y + 2)).unwrap();
+        write!(self.stdout, "{}", self.goto(self.x, self.y)).unwrap();
         self.stdout.flush().unwrap();
-
-        // Reset the grid.
-        for i in 0..self.grid.len() {
-            // Fill it with random, concealed fields.
-            self.grid[i] = Cell {
-                mine: false,
-                revealed: false,
-                observed: false,
-                flagged: false,
-            };
-
-            self.points = 0;
-        }
     }
 
-    /// Get the value of a cell.
+    /// Print the point count.
     ///
-    /// The value represent the sum of adjacent cells containing mines. A cell of value, 0, is
-    /// called "free".
-    fn val(&mut self, x: u16, y: u16) -> u8 {
-        // To avoid nightly version, we manually sum the adjacent mines.
-        let mut res = 0;
-        for &(x, y) in self.adjacent(x, y).iter() {
-            res += self.get(x, y).mine as u8;
+    /// The number of mines left (that is, not flagged yet) is printed next to it. In practice
+    /// mode, so is the number of undos, and the number of hints once one was asked for. The time
+    /// played so far is printed at the other end of the lower frame, if there is room for it.
+    fn print_points(&mut self) {
+        let height = self.board.height();
+        let columns = self.board.topology().columns(self.board.width());
+        // There may be more flags than mines, if some are wrong.
+        let left = self.board.count_mines() as i64 - self.board.count_flags() as i64;
+        let mut status = format!("{} ~ {} mines left", self.board.points(), left);
+        if self.practice {
+            status.push_str(&format!(" ~ {} undos", self.undos));
+        }
+        if self.hints > 0 {
+            status.push_str(&format!(" ~ {} hints", self.hints));
         }
-        res
-    }
-
-    /// Reveal the cell, _c_.
-    ///
-    /// This will recursively reveal free cells, until non-free cell is reached, terminating the
-    /// current recursion descendant.
-    fn reveal(&mut self, x: u16, y: u16) {
-        let v = self.val(x, y);
-
-        self.get_mut(x, y).revealed = true;
-
-        write!(self.stdout, "{}", cursor::Goto(x + 2, y + 2)).unwrap();
 
-        if v == 0 {
-            // If the cell is free, simply put a space on the position.
-            self.stdout.write(b" ").unwrap();
+        write!(self.stdout, "{}{}", cursor::Goto(3, height + 2), status).unwrap();
+        // Points go down when undoing, so make sure no stale digits are left behind.
+        for _ in status.chars().count()..columns.saturating_sub(1) as usize {
+            self.stdout.write(HORZ_BOUNDARY.as_bytes()).unwrap();
+        }
 
-            // Recursively reveal adjacent cells until a non-free cel is reached.
-            for &(x, y) in self.adjacent(x, y).iter() {
-                if !self.get(x, y).revealed && !self.get(x, y).mine {
-                    self.reveal(x, y);
-                }
+        if self.timer.is_some() {
+            let time = scores::format_time(self.elapsed());
+            let len = time.len() as u16;
+            if columns >= len + 10 {
+                write!(self.stdout, "{}{}", cursor::Goto(columns + 1 - len, height + 2), time).unwrap();
             }
-        } else {
-            // Aww. The cell was not free. Print the value instead.
-            self.stdout.write(&[b'0' + v]).unwrap();
         }
     }
 
-    /// Print the point count.
-    fn print_points(&mut self) {
-        let height = self.height();
-        write!(self.stdout, "{}", cursor::Goto(3, height + 2)).unwrap();
-        self.stdout.write(self.points.to_string().as_bytes()).unwrap();
+    /// Get the time played so far, in milliseconds.
+    fn elapsed(&self) -> u64 {
+        self.timer.map_or(0, |timer| millis(timer.elapsed()))
     }
 
     /// Reveal all the fields, printing where the mines were.
     fn reveal_all(&mut self) {
         write!(self.stdout, "{}", cursor::Goto(1, 1)).unwrap();
 
-        for y in 0..self.height() {
-            for x in 0..self.width {
-                write!(self.stdout, "{}", cursor::Goto(x + 2, y + 2)).unwrap();
-                if self.get(x, y).mine {
-                    self.stdout.write(MINE.as_bytes()).unwrap();
+        for y in 0..self.board.height() {
+            for x in 0..self.board.width() {
+                if self.board.cell(x, y).mine {
+                    write!(self.stdout, "{}{}", self.goto(x, y), MINE).unwrap();
                 }
             }
         }
     }
 
+    /// Blow up the mine at (x, y), revealing all the mines.
+    fn explode(&mut self, x: u16, y: u16) {
+        self.reveal_all();
+        // Make the background colour of the mine we just
+        // landed on red, and the foreground black.
+        write!(self.stdout, "{}{}{}{}{}",
+               self.goto(x, y),
+               color::Bg(color::Red), color::Fg(color::Black),
+               MINE,
+               style::Reset).unwrap();
+    }
+
     /// Game over!
-    fn game_over(&mut self) {
+    ///
+    /// In practice mode, the player may undo the fatal action instead of replaying or quitting.
+    /// Returns true if they did so, and the game goes on.
+    fn game_over(&mut self) -> bool {
         //Goto top left corner
         write!(self.stdout, "{}", cursor::Goto(1, 1)).unwrap();
 
         self.stdout.write(GAME_OVER.as_bytes()).unwrap();
+        self.print_code();
+
+        let undo = self.practice && !self.history.is_empty();
+        if undo {
+            write!(self.stdout, "\n\r{}Press u to undo.", clear::UntilNewline).unwrap();
+        }
+        self.stdout.flush().unwrap();
+
+        if self.replay_or_quit(undo) {
+            self.perform(Input::Undo);
+            self.redraw();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// The player won!
+    ///
+    /// This shows the time it took, lets the player enter their initials if it is a high score,
+    /// and shows the high-score table.
+    fn win(&mut self) {
+        let time = self.elapsed();
+        let config = (self.board.width(), self.board.height(), self.board.mines(), self.board.topology());
+        let mut scores = Scores::load();
+
+        write!(self.stdout, "{}{}{}\n\r\n\rTime: {}",
+               cursor::Goto(1, 1), WON, clear::AfterCursor, scores::format_time(time)).unwrap();
+        self.print_code();
+        self.stdout.write(b"\n\r\n\r").unwrap();
+
+        if scores.qualifies(config, time) {
+            self.stdout.write(b"New high score! Initials: ").unwrap();
+            if let Some(initials) = self.read_initials() {
+                scores.insert(Score {
+                    config: config,
+                    time: time,
+                    undos: self.undos,
+                    initials: initials,
+                });
+
+                if let Err(err) = scores.save() {
+                    write!(self.stdout, "\n\rcould not save high scores: {}", err).unwrap();
+                }
+            }
+            self.stdout.write(b"\n\r\n\r").unwrap();
+        }
+
+        scores.write_table(&mut self.stdout, config, "\n\r").unwrap();
         self.stdout.flush().unwrap();
 
+        self.replay_or_quit(false);
+    }
+
+    /// Read up to three initials, echoing them.
+    ///
+    /// Returns `None` if the player pressed escape instead.
+    fn read_initials(&mut self) -> Option<String> {
+        let mut initials = String::new();
+        self.stdout.flush().unwrap();
+
+        loop {
+            match self.stdin.next().unwrap().unwrap() {
+                Event::Key(Key::Char('\n')) if !initials.is_empty() => return Some(initials),
+                Event::Key(Key::Char(c)) if c.is_ascii_alphanumeric() && initials.len() < 3 => {
+                    let c = c.to_ascii_uppercase();
+                    initials.push(c);
+                    write!(self.stdout, "{}", c).unwrap();
+                },
+                Event::Key(Key::Backspace) => if initials.pop().is_some() {
+                    write!(self.stdout, "{} {}", cursor::Left(1), cursor::Left(1)).unwrap();
+                },
+                Event::Key(Key::Esc) => return None,
+                _ => {},
+            }
+
+            self.stdout.flush().unwrap();
+        }
+    }
+
+    /// Print the board code on the next line, so the board can be shared.
+    fn print_code(&mut self) {
+        if let Some(code) = self.board.code() {
+            write!(self.stdout, "\n\r{}Board: {}", clear::UntilNewline, code).unwrap();
+        }
+    }
+
+    /// Wait for the player to either replay or quit.
+    ///
+    /// If _undo_ is set, the player may also choose to undo, in which case this returns true.
+    fn replay_or_quit(&mut self, undo: bool) -> bool {
         loop {
             // Repeatedly read a single byte.
             match self.stdin.next().unwrap().unwrap() {
-                Key::Char('r') => {
+                Event::Key(Key::Char('r')) => {
                     // Replay!
                     self.restart();
-                    return;
+                    return false;
                 },
-                Key::Char('q') => return,
+                Event::Key(Key::Char('q')) => return false,
+                Event::Key(Key::Char('u')) if undo => return true,
                 _ => {},
             }
         }
@@ -423,49 +1202,18 @@ impl<R: Iterator<Item=Result<Key, std::io::Error>>, W: Write> Game<R, W> {
 
     /// Restart (replay) the game.
     fn restart(&mut self) {
+        self.write_replay();
         self.reset();
         self.start();
     }
 
-    /// Calculate the adjacent cells.
-    fn adjacent(&self, x: u16, y: u16) -> [(u16, u16); 8] {
-        let left = self.left(x);
-        let right = self.right(x);
-        let up = self.up(y);
-        let down = self.down(y);
-
-        [
-            // Left-up
-            (left, up),
-            // Up
-            (x, up),
-            // Right-up
-            (right, up),
-            // Left
-            (left, y),
-            // Right
-            (right, y),
-            // Left-down
-            (left, down),
-            // Down
-            (x, down),
-            // Right-down
-            (right, down)
-        ]
-    }
-
-    /// Calculate the height (number of rows) of the grid.
-    fn height(&self) -> u16 {
-        (self.grid.len() / self.width as usize) as u16
-    }
-
     /// Calculate the y coordinate of the cell "above" a given y coordinate.
     ///
-    /// This wraps when _y = 0_.
+    /// This is used for moving the cursor, which wraps when _y = 0_ whatever the topology.
     fn up(&self, y: u16) -> u16 {
         if y == 0 {
             // Upper bound reached. Wrap around.
-            self.height() - 1
+            self.board.height() - 1
         } else {
             y - 1
         }
@@ -474,7 +1222,7 @@ impl<R: Iterator<Item=Result<Key, std::io::Error>>, W: Write> Game<R, W> {
     ///
     /// This wraps when _y = h - 1_.
     fn down(&self, y: u16) -> u16 {
-        if y + 1 == self.height() {
+        if y + 1 == self.board.height() {
             // Lower bound reached. Wrap around.
             0
         } else {
@@ -487,7 +1235,7 @@ impl<R: Iterator<Item=Result<Key, std::io::Error>>, W: Write> Game<R, W> {
     fn left(&self, x: u16) -> u16 {
         if x == 0 {
             // Lower bound reached. Wrap around.
-            self.width - 1
+            self.board.width() - 1
         } else {
             x - 1
         }
@@ -496,7 +1244,7 @@ impl<R: Iterator<Item=Result<Key, std::io::Error>>, W: Write> Game<R, W> {
     ///
     /// This wraps when _x = w - 1_.
     fn right(&self, x: u16) -> u16 {
-        if x + 1 == self.width {
+        if x + 1 == self.board.width() {
             // Upper bound reached. Wrap around.
             0
         } else {
@@ -505,11 +1253,41 @@ impl<R: Iterator<Item=Result<Key, std::io::Error>>, W: Write> Game<R, W> {
     }
 }
 
+/// Get the size of the largest grid of the given topology fitting in the terminal, if its size is
+/// known.
+fn terminal_grid(topology: Topology) -> Option<(u16, u16)> {
+    termion::terminal_size().ok().map(|(w, h)| (topology.fit(w.saturating_sub(2)), h.saturating_sub(2)))
+}
+
+/// Convert a duration to milliseconds.
+fn millis(duration: Duration) -> u64 {
+    duration.as_secs() * 1000 + (duration.subsec_nanos() / 1_000_000) as u64
+}
+
+/// Read a random 64-bit number.
+fn rand_u64(rand: &mut Randomizer) -> u64 {
+    let mut r = 0;
+    for _ in 0..8 {
+        r = r << 8 | rand.read_u8() as u64;
+    }
+    r
+}
+
 fn main() {
     let mut args = env::args().skip(1);
     let mut width = None;
     let mut height = None;
-    let mut diff = 6;
+    // The share of cells holding a mine, unless an exact number of mines is given.
+    let mut density = 1.0 / 6.0;
+    let mut mines = None;
+    let mut no_guess = false;
+    let mut load = None;
+    let mut topology = None;
+    let mut seed = None;
+    let mut board = None;
+    let mut practice = false;
+    let mut replay = None;
+    let mut speed = 1.0;
 
     // Get and lock the stdios.
     let stdout = io::stdout();
@@ -566,10 +1344,140 @@ fn main() {
                 stdout.flush().unwrap();
                 process::exit(0);
             },
-            "-g" => diff = 2,
-            "-a" => diff = 4,
-            "-i" => diff = 6,
-            "-b" => diff = 10,
+            "--scores" => {
+                // Print the high scores of every configuration.
+                let scores = Scores::load();
+                let configs = scores.configs();
+                if configs.is_empty() {
+                    stdout.write(b"no high scores yet.\n").unwrap();
+                }
+                for config in configs {
+                    scores.write_table(&mut stdout, config, "\n").unwrap();
+                }
+                stdout.flush().unwrap();
+                process::exit(0);
+            },
+            "-g" => {
+                density = 1.0 / 2.0;
+                mines = None;
+            },
+            "-a" => {
+                density = 1.0 / 4.0;
+                mines = None;
+            },
+            "-i" => {
+                density = 1.0 / 6.0;
+                mines = None;
+            },
+            "-b" => {
+                density = 1.0 / 10.0;
+                mines = None;
+            },
+            "--mines" => mines = Some(args.next().unwrap_or_else(|| {
+                stderr.write(b"no number of mines given.\n").unwrap();
+                stderr.flush().unwrap();
+                process::exit(1);
+            }).parse().unwrap_or_else(|_| {
+                stderr.write(b"invalid integer given.\n").unwrap();
+                stderr.flush().unwrap();
+                process::exit(1);
+            })),
+            "--density" => {
+                let percent: f64 = args.next().unwrap_or_else(|| {
+                    stderr.write(b"no density given.\n").unwrap();
+                    stderr.flush().unwrap();
+                    process::exit(1);
+                }).parse().unwrap_or_else(|_| {
+                    stderr.write(b"invalid number given.\n").unwrap();
+                    stderr.flush().unwrap();
+                    process::exit(1);
+                });
+                if !(percent > 0.0 && percent < 100.0) {
+                    stderr.write(b"the density must be between 0 and 100 percent.\n").unwrap();
+                    stderr.flush().unwrap();
+                    process::exit(1);
+                }
+
+                density = percent / 100.0;
+                mines = None;
+            },
+            "--no-guess" => no_guess = true,
+            "--practice" => practice = true,
+            "--seed" => if seed.is_none() {
+                seed = Some(args.next().unwrap_or_else(|| {
+                    stderr.write(b"no seed given.\n").unwrap();
+                    stderr.flush().unwrap();
+                    process::exit(1);
+                }).parse().unwrap_or_else(|_| {
+                    stderr.write(b"invalid integer given.\n").unwrap();
+                    stderr.flush().unwrap();
+                    process::exit(1);
+                }));
+            } else {
+                stderr.write(b"you may only input one seed.\n").unwrap();
+                stderr.flush().unwrap();
+                process::exit(1);
+            },
+            "--board" => if board.is_none() {
+                board = Some(args.next().and_then(|code| BoardCode::parse(&code)).unwrap_or_else(|| {
+                    stderr.write(b"no valid board code given.\n").unwrap();
+                    stderr.flush().unwrap();
+                    process::exit(1);
+                }));
+            } else {
+                stderr.write(b"you may only input one board code.\n").unwrap();
+                stderr.flush().unwrap();
+                process::exit(1);
+            },
+            "--topology" => if topology.is_none() {
+                topology = Some(args.next().and_then(|name| Topology::from_name(&name)).unwrap_or_else(|| {
+                    stderr.write(b"no valid topology given (square, torus or hex).\n").unwrap();
+                    stderr.flush().unwrap();
+                    process::exit(1);
+                }));
+            } else {
+                stderr.write(b"you may only input one topology.\n").unwrap();
+                stderr.flush().unwrap();
+                process::exit(1);
+            },
+            "--replay" => if replay.is_none() {
+                replay = Some(args.next().unwrap_or_else(|| {
+                    stderr.write(b"no replay file given.\n").unwrap();
+                    stderr.flush().unwrap();
+                    process::exit(1);
+                }));
+            } else {
+                stderr.write(b"you may only watch one replay.\n").unwrap();
+                stderr.flush().unwrap();
+                process::exit(1);
+            },
+            "--speed" => {
+                speed = args.next().unwrap_or_else(|| {
+                    stderr.write(b"no speed given.\n").unwrap();
+                    stderr.flush().unwrap();
+                    process::exit(1);
+                }).parse().unwrap_or_else(|_| {
+                    stderr.write(b"invalid number given.\n").unwrap();
+                    stderr.flush().unwrap();
+                    process::exit(1);
+                });
+                if !(speed >= MIN_SPEED && speed <= MAX_SPEED) {
+                    writeln!(stderr, "the speed must be between {} and {}.", MIN_SPEED, MAX_SPEED).unwrap();
+                    stderr.flush().unwrap();
+                    process::exit(1);
+                }
+            },
+            "--load" => if load.is_none() {
+                load = Some(args.next().unwrap_or_else(|| {
+                    stderr.write(b"no save file given.\n").unwrap();
+                    stderr.flush().unwrap();
+                    process::exit(1);
+                }));
+            } else {
+                stderr.write(b"you may only load one save file.\n").unwrap();
+                stderr.flush().unwrap();
+                process::exit(1);
+            },
             _ => {
                 stderr.write(b"Unknown argument.\n").unwrap();
                 stderr.flush().unwrap();
@@ -578,13 +1486,224 @@ fn main() {
         }
     }
 
+    // Watch a replay instead of playing, if asked to.
+    if let Some(path) = replay {
+        let replay = File::open(&path).map_err(save::Error::Io).and_then(|file| {
+            Replay::read(BufReader::new(file))
+        }).unwrap_or_else(|err| {
+            writeln!(stderr, "{}: {}", path, err).unwrap();
+            stderr.flush().unwrap();
+            process::exit(1);
+        });
+
+        // Replays are played in real time, so the keys are read without waiting for them.
+        drop(stdin);
+        let stdout = stdout.into_raw_mode().unwrap();
+        watch(stdout, termion::async_stdin(), replay, speed);
+        return;
+    }
+
+    // Read the save file, if any. The saved grid takes precedence over the terminal size.
+    let save = load.as_ref().map(|path| {
+        let save = File::open(path).map_err(save::Error::Io).and_then(|file| {
+            Save::read(BufReader::new(file))
+        }).unwrap_or_else(|err| {
+            writeln!(stderr, "{}: {}", path, err).unwrap();
+            stderr.flush().unwrap();
+            process::exit(1);
+        });
+
+        if width.map_or(false, |w| w != save.width) || height.map_or(false, |h| h != save.height) {
+            writeln!(stderr, "{}: the saved grid does not match the given size.", path).unwrap();
+            stderr.flush().unwrap();
+            process::exit(1);
+        }
+        if topology.map_or(false, |t| t != save.topology) {
+            writeln!(stderr, "{}: the saved grid does not match the given topology.", path).unwrap();
+            stderr.flush().unwrap();
+            process::exit(1);
+        }
+
+        width = Some(save.width);
+        height = Some(save.height);
+        mines = Some(save.mines);
+        topology = Some(save.topology);
+        save
+    });
+
+    // A board code fixes everything the mine placement depends on.
+    if let Some(code) = board {
+        if load.is_some() || seed.is_some() {
+            stderr.write(b"a board code cannot be combined with a save file or a seed.\n").unwrap();
+            stderr.flush().unwrap();
+            process::exit(1);
+        }
+        if width.map_or(false, |w| w != code.width) || height.map_or(false, |h| h != code.height)
+            || topology.map_or(false, |t| t != code.topology) {
+            stderr.write(b"the board code does not match the given size or topology.\n").unwrap();
+            stderr.flush().unwrap();
+            process::exit(1);
+        }
+
+        width = Some(code.width);
+        height = Some(code.height);
+        mines = Some(code.mines);
+        topology = Some(code.topology);
+        no_guess = code.no_guess;
+        seed = Some(code.seed);
+    }
+
+    let topology = topology.unwrap_or(Topology::Torus);
+    let termsize = terminal_grid(topology);
+    let width = width.or(termsize.map(|(w, _)| w)).unwrap_or(70);
+    let height = height.or(termsize.map(|(_, h)| h)).unwrap_or(40);
+
+    // Count the mines, which must leave room for the first click.
+    let cells = width as u64 * height as u64;
+    let mines = mines.unwrap_or_else(|| (cells as f64 * density).round() as u32);
+    if mines as u64 >= cells {
+        stderr.write(b"too many mines for the grid.\n").unwrap();
+        stderr.flush().unwrap();
+        process::exit(1);
+    }
+
     // We go to raw mode to make the control over the terminal more fine-grained.
-    let stdout = stdout.into_raw_mode().unwrap();
+    let stdout = MouseTerminal::from(stdout.into_raw_mode().unwrap());
 
-    let termsize = termion::terminal_size().ok();
-    let termwidth = termsize.map(|(w,_)| w - 2);
-    let termheight = termsize.map(|(_,h)| h - 2);
     // Initialize the game!
-    init(stdout, stdin, diff, width.or(termwidth).unwrap_or(70),
-                              height.or(termheight).unwrap_or(40));
+    init(stdout, stdin, mines, topology, no_guess, width, height,
+         load.unwrap_or_else(|| SAVE_FILE.to_owned()), save, seed, board.map(|code| (code.x, code.y)),
+         practice);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::iter::{self, Empty};
+
+    /// A game with no input, drawing into a buffer.
+    type TestGame = Game<Empty<Result<Event, io::Error>>, Vec<u8>>;
+
+    /// Start a game in practice mode, on a randomly seeded 9x9 board with 10 mines.
+    fn game() -> TestGame {
+        let mut game = Game {
+            board: Board::new(9, 9, 10, Topology::Square, false),
+            x: 0,
+            y: 0,
+            rand: Randomizer::new(0),
+            seed: None,
+            practice: true,
+            history: Vec::new(),
+            undos: 0,
+            hints: 0,
+            hinted: None,
+            save_file: String::new(),
+            timer: None,
+            replay_start: None,
+            recording: Instant::now(),
+            inputs: Vec::new(),
+            left_held: false,
+            right_held: false,
+            stdin: iter::empty(),
+            stdout: Vec::new(),
+        };
+        game.reset();
+        game
+    }
+
+    /// Find a concealed cell, with or without a mine.
+    fn find(game: &TestGame, mine: bool) -> (u16, u16) {
+        let c = game.board.cells().iter()
+            .position(|cell| cell.mine == mine && !cell.revealed && !cell.flagged)
+            .unwrap();
+        game.board.coords(c)
+    }
+
+    /// Get the mouse event of a button pressed on the cell at (x, y).
+    fn press(button: MouseButton, x: u16, y: u16) -> MouseEvent {
+        let (col, row) = Topology::Square.screen(x, y);
+        MouseEvent::Press(button, col, row)
+    }
+
+    /// Get the mouse event of a button released on the cell at (x, y).
+    fn release(x: u16, y: u16) -> MouseEvent {
+        let (col, row) = Topology::Square.screen(x, y);
+        MouseEvent::Release(col, row)
+    }
+
+    #[test]
+    fn mouse_clicks_on_release() {
+        let mut game = game();
+        assert!(game.mouse(press(MouseButton::Left, 4, 4)));
+        assert!(!game.board.started());
+        assert_eq!((game.x, game.y), (4, 4));
+
+        assert!(game.mouse(release(4, 4)));
+        assert!(game.board.cell(4, 4).revealed);
+
+        // Pressing both buttons on a concealed cell only chords it, which does nothing, even on
+        // a mine.
+        let (x, y) = find(&game, true);
+        assert!(game.mouse(press(MouseButton::Left, x, y)));
+        assert!(game.mouse(press(MouseButton::Right, x, y)));
+        assert!(game.mouse(release(x, y)));
+        assert!(game.mouse(release(x, y)));
+        assert_eq!(game.board.state(), State::Playing);
+        assert!(!game.board.cell(x, y).revealed && !game.board.cell(x, y).flagged);
+
+        // The right button alone marks right away.
+        assert!(game.mouse(press(MouseButton::Right, x, y)));
+        assert!(game.board.cell(x, y).flagged);
+        assert!(game.mouse(release(x, y)));
+        assert!(game.board.cell(x, y).flagged);
+    }
+
+    #[test]
+    fn undoing_the_first_click_keeps_the_mines() {
+        let mut game = game();
+        game.perform(Input::Click(4, 4));
+        let mines = game.board.cells().to_vec();
+
+        game.perform(Input::Undo);
+        assert!(game.board.started());
+        assert!(game.board.cells().iter().all(|cell| !cell.revealed));
+
+        game.perform(Input::Click(0, 0));
+        let same = game.board.cells().iter().zip(mines.iter()).all(|(a, b)| a.mine == b.mine);
+        assert!(same);
+    }
+
+    #[test]
+    fn playback_gives_the_same_board() {
+        let mut game = game();
+        game.perform(Input::Click(4, 4));
+        game.perform(Input::Undo);
+        game.perform(Input::Click(4, 4));
+
+        let (x, y) = find(&game, true);
+        game.perform(Input::Mark(x, y));
+        let (x, y) = find(&game, false);
+        game.perform(Input::Click(x, y));
+        let (x, y) = find(&game, true);
+        assert!(!game.perform(Input::Click(x, y)));
+        game.perform(Input::Undo);
+        game.perform(Input::Cursor(0, 8));
+        game.perform(Input::Hint);
+
+        let mut file = Vec::new();
+        game.replay().unwrap().write(&mut file).unwrap();
+        let replay = Replay::read(&file[..]).unwrap();
+
+        let mut watched = self::game();
+        watched.rewind(&replay);
+        for &(_, input) in replay.inputs.iter() {
+            watched.play(input);
+        }
+
+        assert_eq!(watched.board.cells(), game.board.cells());
+        assert_eq!(watched.board.points(), game.board.points());
+        assert_eq!(watched.board.state(), game.board.state());
+        assert_eq!((watched.x, watched.y), (game.x, game.y));
+        assert_eq!((watched.undos, watched.hints), (game.undos, game.hints));
+    }
 }
@@ -0,0 +1,188 @@
+//! Recording and reading replays.
+//!
+//! A replay is the game as it was when the recording started, with the mines already in place,
+//! followed by every input of the player and the time it was made at. Playing the inputs back on
+//! the starting position gives the very same game. A replay file looks like this:
+//!
+//! ```text
+//! minesweeper-replay 1
+//! practice 0
+//! minesweeper-save 8
+//! ...
+//! 0 click 3 1
+//! 1250 cursor 2 1
+//! 1900 mark 2 1
+//! ```
+//!
+//! The starting position is written like a save file (see `save`), and every input takes a
+//! line, with the time in milliseconds since the recording started.
+
+use std::io::{self, BufRead, Write};
+
+use save::{Error, Save};
+
+/// The first line of every replay file.
+const MAGIC: &'static str = "minesweeper-replay";
+/// The current version of the format.
+const VERSION: u32 = 1;
+
+/// An input of the player.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Input {
+    /// Move the cursor to (x, y).
+    Cursor(u16, u16),
+    /// Click the cell at (x, y).
+    Click(u16, u16),
+    /// Chord the cell at (x, y).
+    Chord(u16, u16),
+    /// Cycle the mark of the cell at (x, y).
+    Mark(u16, u16),
+    /// Undo the last action.
+    Undo,
+    /// Ask for a hint.
+    Hint,
+}
+
+/// A recorded game.
+#[derive(PartialEq, Debug)]
+pub struct Replay {
+    /// Was the game played in practice mode?
+    pub practice: bool,
+    /// The game as it was when the recording started, with the mines in place.
+    pub start: Save,
+    /// The inputs, with the time they were made at, in milliseconds since the start.
+    pub inputs: Vec<(u64, Input)>,
+}
+
+impl Replay {
+    /// Write the replay file.
+    pub fn write<W: Write>(&self, mut to: W) -> io::Result<()> {
+        writeln!(to, "{} {}", MAGIC, VERSION)?;
+        writeln!(to, "practice {}", self.practice as u8)?;
+        self.start.write(&mut to)?;
+
+        for &(time, input) in self.inputs.iter() {
+            match input {
+                Input::Cursor(x, y) => writeln!(to, "{} cursor {} {}", time, x, y)?,
+                Input::Click(x, y) => writeln!(to, "{} click {} {}", time, x, y)?,
+                Input::Chord(x, y) => writeln!(to, "{} chord {} {}", time, x, y)?,
+                Input::Mark(x, y) => writeln!(to, "{} mark {} {}", time, x, y)?,
+                Input::Undo => writeln!(to, "{} undo", time)?,
+                Input::Hint => writeln!(to, "{} hint", time)?,
+            }
+        }
+
+        to.flush()
+    }
+
+    /// Read a replay file.
+    pub fn read<R: BufRead>(mut from: R) -> Result<Replay, Error> {
+        let mut line = String::new();
+        from.read_line(&mut line)?;
+        if line.trim() != format!("{} {}", MAGIC, VERSION) {
+            return Err(Error::Version);
+        }
+
+        line.clear();
+        from.read_line(&mut line)?;
+        let practice = match line.trim() {
+            "practice 0" => false,
+            "practice 1" => true,
+            _ => return Err(Error::Corrupt(2, "expected practice 0 or 1")),
+        };
+
+        // The starting position counts its lines from its own start.
+        let start = Save::read(&mut from).map_err(|err| match err {
+            Error::Corrupt(line, msg) => Error::Corrupt(line + 2, msg),
+            err => err,
+        })?;
+        if !start.started {
+            return Err(Error::Corrupt(2, "the mines are not placed"));
+        }
+
+        let (width, height) = (start.width, start.height);
+        let mut inputs = Vec::new();
+        let mut number = 2 + start.lines();
+        let mut last = 0;
+        for line in from.lines() {
+            let line = line?;
+            number += 1;
+
+            let words: Vec<&str> = line.split_whitespace().collect();
+            if words.is_empty() {
+                continue;
+            }
+
+            let numbers: Vec<u64> = match words.iter().enumerate()
+                .filter(|&(i, _)| i != 1)
+                .map(|(_, word)| word.parse())
+                .collect() {
+                Ok(numbers) => numbers,
+                Err(_) => return Err(Error::Corrupt(number, "invalid number")),
+            };
+
+            let time = numbers[0];
+            if time < last {
+                return Err(Error::Corrupt(number, "the inputs are out of order"));
+            }
+            last = time;
+
+            let input = match (words.get(1).cloned(), &numbers[1..]) {
+                (Some("undo"), &[]) => Input::Undo,
+                (Some("hint"), &[]) => Input::Hint,
+                (Some(name), &[x, y]) => {
+                    if x >= width as u64 || y >= height as u64 {
+                        return Err(Error::Corrupt(number, "the cell is outside the grid"));
+                    }
+
+                    let (x, y) = (x as u16, y as u16);
+                    match name {
+                        "cursor" => Input::Cursor(x, y),
+                        "click" => Input::Click(x, y),
+                        "chord" => Input::Chord(x, y),
+                        "mark" => Input::Mark(x, y),
+                        _ => return Err(Error::Corrupt(number, "unknown input")),
+                    }
+                },
+                _ => return Err(Error::Corrupt(number, "unknown input")),
+            };
+
+            inputs.push((time, input));
+        }
+
+        Ok(Replay {
+            practice: practice,
+            start: start,
+            inputs: inputs,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn written_replay_reads_back() {
+        let start = Save::read("minesweeper-save 8\nsize 4 2\nmines 2\ntopology square\n\
+                                cursor 1 0\npoints 3\nstarted 1\ntime 42100\nundos 0\nhints 2\n\
+                                board s4x2-2-3.1-9f3a1c2e04b7d615\nrand 1234567890\n5220\n1024\n"
+                                    .as_bytes()).unwrap();
+        let replay = Replay {
+            practice: true,
+            start: start,
+            inputs: vec![
+                (0, Input::Cursor(2, 1)),
+                (250, Input::Click(2, 1)),
+                (900, Input::Mark(3, 0)),
+                (900, Input::Chord(2, 1)),
+                (1400, Input::Undo),
+                (2000, Input::Hint),
+            ],
+        };
+
+        let mut file = Vec::new();
+        replay.write(&mut file).unwrap();
+        assert_eq!(Replay::read(&file[..]).unwrap(), replay);
+    }
+}
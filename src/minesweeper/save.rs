@@ -0,0 +1,400 @@
+//! Saving and loading of games in progress.
+//!
+//! A save file is a small, versioned text file. It looks like this:
+//!
+//! ```text
+//! minesweeper-save 8
+//! size 4 2
+//! mines 2
+//! topology square
+//! cursor 1 0
+//! points 3
+//! started 1
+//! time 42100
+//! undos 0
+//! hints 2
+//! board s4x2-2-3.1-9f3a1c2e04b7d615
+//! rand 1234567890
+//! 5220
+//! 1024
+//! ```
+//!
+//! The header is followed by one line per row of the grid, with one digit per cell. The digit is
+//! the sum of 1 if the cell holds a mine, 2 if it is revealed, 4 if it is flagged, and 8 if it is
+//! marked with a question mark.
+
+use std::fmt;
+use std::io::{self, BufRead, Write};
+
+use board::Cell;
+use code::BoardCode;
+use topology::Topology;
+
+/// The first line of every save file.
+const MAGIC: &'static str = "minesweeper-save";
+/// The current version of the format.
+const VERSION: u32 = 8;
+/// The number of lines before the grid.
+const HEADER_LINES: usize = 12;
+
+/// The saved state of a game.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Save {
+    /// Width of the grid.
+    pub width: u16,
+    /// Height of the grid.
+    pub height: u16,
+    /// The number of mines to place.
+    pub mines: u32,
+    /// The shape of the board.
+    pub topology: Topology,
+    /// The x coordinate of the cursor.
+    pub x: u16,
+    /// The y coordinate of the cursor.
+    pub y: u16,
+    /// Points.
+    pub points: u16,
+    /// Are the mines placed yet?
+    pub started: bool,
+    /// The time played so far, in milliseconds.
+    pub time: u64,
+    /// The number of actions undone so far.
+    pub undos: u16,
+    /// The number of hints asked for so far.
+    pub hints: u16,
+    /// The code of the board, if the mines are placed.
+    pub board: Option<BoardCode>,
+    /// The seed of the randomizer.
+    pub rand: u64,
+    /// The grid, enumerated like `Game::grid`.
+    pub grid: Vec<Cell>,
+}
+
+/// An error while loading a save file.
+#[derive(Debug)]
+pub enum Error {
+    /// The file could not be read.
+    Io(io::Error),
+    /// The file is not a save file, or one of a version we do not know.
+    Version,
+    /// The file is malformed.
+    Corrupt(usize, &'static str),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Io(ref err) => write!(f, "could not read save file: {}", err),
+            Error::Version => write!(f, "not a save file of version {}", VERSION),
+            Error::Corrupt(line, msg) => write!(f, "corrupt save file (line {}): {}", line, msg),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        Error::Io(err)
+    }
+}
+
+impl Save {
+    /// Get the number of lines of the save file.
+    pub fn lines(&self) -> usize {
+        HEADER_LINES + self.height as usize
+    }
+
+    /// Write the save file.
+    pub fn write<W: Write>(&self, mut to: W) -> io::Result<()> {
+        writeln!(to, "{} {}", MAGIC, VERSION)?;
+        writeln!(to, "size {} {}", self.width, self.height)?;
+        writeln!(to, "mines {}", self.mines)?;
+        writeln!(to, "topology {}", self.topology.name())?;
+        writeln!(to, "cursor {} {}", self.x, self.y)?;
+        writeln!(to, "points {}", self.points)?;
+        writeln!(to, "started {}", self.started as u8)?;
+        writeln!(to, "time {}", self.time)?;
+        writeln!(to, "undos {}", self.undos)?;
+        writeln!(to, "hints {}", self.hints)?;
+        match self.board {
+            Some(code) => writeln!(to, "board {}", code)?,
+            None => writeln!(to, "board none")?,
+        }
+        writeln!(to, "rand {}", self.rand)?;
+
+        for row in self.grid.chunks(self.width as usize) {
+            for cell in row {
+                let digit = cell.mine as u8 | (cell.revealed as u8) << 1 | (cell.flagged as u8) << 2
+                    | (cell.questioned as u8) << 3;
+                to.write(&[b'0' + digit])?;
+            }
+            to.write(b"\n")?;
+        }
+
+        to.flush()
+    }
+
+    /// Read a save file.
+    pub fn read<R: BufRead>(from: R) -> Result<Save, Error> {
+        let mut lines = Lines {
+            lines: from.lines(),
+            number: 0,
+        };
+
+        if lines.next()? != format!("{} {}", MAGIC, VERSION) {
+            return Err(Error::Version);
+        }
+
+        let size = lines.field("size", 2)?;
+        let (width, height) = (size[0], size[1]);
+        if width == 0 || height == 0 {
+            return Err(lines.corrupt("the grid is empty"));
+        }
+        if width > 0xFFFF || height > 0xFFFF {
+            return Err(lines.corrupt("the grid is too large"));
+        }
+
+        let mines = lines.field("mines", 1)?[0];
+        if mines >= width * height {
+            return Err(lines.corrupt("too many mines"));
+        }
+
+        let topology = match Topology::from_name(&lines.word("topology")?) {
+            Some(topology) => topology,
+            None => return Err(lines.corrupt("unknown topology")),
+        };
+
+        let cursor = lines.field("cursor", 2)?;
+        if cursor[0] >= width || cursor[1] >= height {
+            return Err(lines.corrupt("the cursor is outside the grid"));
+        }
+
+        let points = lines.field("points", 1)?[0];
+        if points > 0xFFFF {
+            return Err(lines.corrupt("invalid points"));
+        }
+
+        let started = match lines.field("started", 1)?[0] {
+            0 => false,
+            1 => true,
+            _ => return Err(lines.corrupt("started must be 0 or 1")),
+        };
+
+        let time = lines.field("time", 1)?[0];
+        let undos = lines.field("undos", 1)?[0];
+        if undos > 0xFFFF {
+            return Err(lines.corrupt("invalid undos"));
+        }
+        let hints = lines.field("hints", 1)?[0];
+        if hints > 0xFFFF {
+            return Err(lines.corrupt("invalid hints"));
+        }
+        let board = match lines.word("board")?.as_ref() {
+            "none" => None,
+            code => match BoardCode::parse(code) {
+                Some(code) => Some(code),
+                None => return Err(lines.corrupt("invalid board code")),
+            },
+        };
+        if board.is_some() != started {
+            return Err(lines.corrupt("started games, and only those, have a board code"));
+        }
+        if let Some(code) = board {
+            if (code.width as u64, code.height as u64, code.mines as u64) != (width, height, mines)
+                || code.topology != topology {
+                return Err(lines.corrupt("the board code does not match the board"));
+            }
+        }
+        let board_line = lines.number;
+        let rand = lines.field("rand", 1)?[0];
+
+        let mut grid = Vec::new();
+        for _ in 0..height {
+            let line = lines.next()?;
+            if line.len() != width as usize {
+                return Err(lines.corrupt("the row does not match the width"));
+            }
+
+            for b in line.bytes() {
+                if b < b'0' || b > b'9' {
+                    return Err(lines.corrupt("invalid cell"));
+                }
+
+                let digit = b - b'0';
+                let cell = Cell {
+                    mine: digit & 1 != 0,
+                    revealed: digit & 2 != 0,
+                    flagged: digit & 4 != 0,
+                    questioned: digit & 8 != 0,
+                };
+
+                if cell.mine && cell.revealed {
+                    return Err(lines.corrupt("a revealed mine"));
+                }
+                if cell.revealed && (cell.flagged || cell.questioned) {
+                    return Err(lines.corrupt("a mark on a revealed cell"));
+                }
+
+                grid.push(cell);
+            }
+        }
+
+        // The mines must be where the first click put them: as many as asked for, unless they
+        // did not fit (or were left out of a no-guess board), and none around the click.
+        let placed = grid.iter().filter(|cell| cell.mine).count() as u64;
+        match board {
+            Some(code) => {
+                let around = topology.neighbours(code.width, code.height, code.x, code.y);
+                let fit = width * height - 1 - around.len() as u64;
+                if placed > mines || (placed < mines.min(fit) && !code.no_guess) {
+                    return Err(Error::Corrupt(board_line, "the number of mines does not match"));
+                }
+
+                let pos = |x: u16, y: u16| y as usize * width as usize + x as usize;
+                let start = grid[pos(code.x, code.y)].mine;
+                if start || around.iter().any(|&(x, y)| grid[pos(x, y)].mine) {
+                    return Err(Error::Corrupt(board_line, "a mine next to the first click"));
+                }
+            },
+            None => if placed > 0 {
+                return Err(Error::Corrupt(board_line, "mines placed before the first click"));
+            },
+        }
+
+        Ok(Save {
+            width: width as u16,
+            height: height as u16,
+            mines: mines as u32,
+            topology: topology,
+            x: cursor[0] as u16,
+            y: cursor[1] as u16,
+            points: points as u16,
+            started: started,
+            time: time,
+            undos: undos as u16,
+            hints: hints as u16,
+            board: board,
+            rand: rand,
+            grid: grid,
+        })
+    }
+}
+
+/// The lines of a save file, keeping track of the line number for errors.
+struct Lines<B> {
+    lines: io::Lines<B>,
+    number: usize,
+}
+
+impl<B: BufRead> Lines<B> {
+    /// Read the next line.
+    fn next(&mut self) -> Result<String, Error> {
+        self.number += 1;
+        match self.lines.next() {
+            Some(line) => Ok(line?.trim().to_owned()),
+            None => Err(self.corrupt("unexpected end of file")),
+        }
+    }
+
+    /// Read a line of the form `name WORD`.
+    fn word(&mut self, name: &str) -> Result<String, Error> {
+        let line = self.next()?;
+        let words: Vec<&str> = line.split_whitespace().collect();
+
+        if words.first() != Some(&name) {
+            return Err(self.corrupt("unexpected field"));
+        }
+        if words.len() != 2 {
+            return Err(self.corrupt("wrong number of values"));
+        }
+
+        Ok(words[1].to_owned())
+    }
+
+    /// Read a line of the form `name N...`, with _n_ numbers.
+    fn field(&mut self, name: &str, n: usize) -> Result<Vec<u64>, Error> {
+        let line = self.next()?;
+        let mut words = line.split_whitespace();
+
+        if words.next() != Some(name) {
+            return Err(self.corrupt("unexpected field"));
+        }
+
+        let mut values = Vec::with_capacity(n);
+        for word in words {
+            values.push(word.parse().map_err(|_| self.corrupt("invalid number"))?);
+        }
+
+        if values.len() != n {
+            return Err(self.corrupt("wrong number of values"));
+        }
+
+        Ok(values)
+    }
+
+    /// Make an error pointing at the current line.
+    fn corrupt(&self, msg: &'static str) -> Error {
+        Error::Corrupt(self.number, msg)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The save file given as an example above.
+    const EXAMPLE: &'static str = "minesweeper-save 8\nsize 4 2\nmines 2\ntopology square\n\
+                                   cursor 1 0\npoints 3\nstarted 1\ntime 42100\nundos 0\n\
+                                   hints 2\nboard s4x2-2-3.1-9f3a1c2e04b7d615\nrand 1234567890\n\
+                                   5220\n1024\n";
+
+    /// Read the example, with a line replaced.
+    fn read_with(old: &str, new: &str) -> Result<Save, Error> {
+        assert!(EXAMPLE.contains(old));
+        Save::read(EXAMPLE.replacen(old, new, 1).as_bytes())
+    }
+
+    /// Get the message of a corrupt-file error.
+    fn corrupt(result: Result<Save, Error>) -> &'static str {
+        match result {
+            Err(Error::Corrupt(_, msg)) => msg,
+            _ => panic!("the file is not found corrupt"),
+        }
+    }
+
+    #[test]
+    fn example_reads_back() {
+        let save = Save::read(EXAMPLE.as_bytes()).unwrap();
+        assert_eq!((save.width, save.height, save.mines), (4, 2, 2));
+
+        let mut file = Vec::new();
+        save.write(&mut file).unwrap();
+        assert_eq!(String::from_utf8(file).unwrap(), EXAMPLE);
+    }
+
+    #[test]
+    fn board_code_must_match() {
+        let mismatch = "the board code does not match the board";
+        assert_eq!(corrupt(read_with("size 4 2\n", "size 4 3\n")), mismatch);
+        assert_eq!(corrupt(read_with("mines 2", "mines 1")), mismatch);
+        assert_eq!(corrupt(read_with("topology square", "topology torus")), mismatch);
+    }
+
+    #[test]
+    fn mines_must_match() {
+        let board = "board s4x2-2-3.1-9f3a1c2e04b7d615";
+        assert_eq!(corrupt(read_with("5220", "4220")), "the number of mines does not match");
+        assert_eq!(corrupt(read_with("5220", "5221")), "the number of mines does not match");
+        assert_eq!(corrupt(read_with(board, "board s4x2-2-0.1-9f3a1c2e04b7d615")),
+                   "a mine next to the first click");
+
+        // A no-guess board may have lost mines on the way.
+        let no_guess = EXAMPLE.replacen("5220", "4220", 1)
+            .replacen(board, "board s4x2-2-3.1-9f3a1c2e04b7d615-ng", 1);
+        assert!(Save::read(no_guess.as_bytes()).is_ok());
+
+        let unstarted = EXAMPLE.replacen("started 1", "started 0", 1)
+            .replacen(board, "board none", 1);
+        assert_eq!(corrupt(Save::read(unstarted.as_bytes())),
+                   "mines placed before the first click");
+    }
+}
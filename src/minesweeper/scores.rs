@@ -0,0 +1,256 @@
+//! The high-score table.
+//!
+//! The best times are kept per configuration, that is per grid size, number of mines and
+//! topology, in a text file in the home directory. The file starts with a header line, and every
+//! other line holds a single score:
+//!
+//! ```text
+//! minesweeper-scores 3
+//! width height mines topology milliseconds initials undos
+//! ```
+//!
+//! Older files are converted on loading. Those of version 2 leave out the topology, and their
+//! scores are taken as made on square boards. Those without a header hold the difficulty instead
+//! of the number of mines too: one cell in _difficulty_ held a mine. The number of undos (see
+//! practice mode) may be left out in them, meaning none.
+
+use std::env;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::PathBuf;
+
+use topology::Topology;
+
+/// The name of the high-score file.
+const SCORES_FILE: &'static str = ".minesweeper_scores";
+/// The number of scores kept per configuration.
+pub const MAX_SCORES: usize = 10;
+/// The first word of the high-score file.
+const MAGIC: &'static str = "minesweeper-scores";
+/// The current version of the format.
+const VERSION: u32 = 3;
+
+/// A grid size, number of mines and topology.
+pub type Config = (u16, u16, u32, Topology);
+
+/// A winning game.
+#[derive(Clone, Debug)]
+pub struct Score {
+    /// The configuration the game was played in.
+    pub config: Config,
+    /// The time it took to win, in milliseconds.
+    pub time: u64,
+    /// The number of actions undone on the way.
+    pub undos: u16,
+    /// The initials of the player.
+    pub initials: String,
+}
+
+impl Score {
+    /// Parse a line of a high-score file of the given version.
+    ///
+    /// In a file without header (version 1), the third number is a difficulty, which is turned
+    /// into the number of mines it placed on average.
+    fn parse(line: &str, version: u32) -> Option<Score> {
+        let mut words = line.split_whitespace();
+        let width: u16 = words.next()?.parse().ok()?;
+        let height: u16 = words.next()?.parse().ok()?;
+        let mines = if version < 2 {
+            let difficulty: u8 = words.next()?.parse().ok()?;
+            if difficulty == 0 {
+                return None;
+            }
+            (width as f64 * height as f64 / difficulty as f64).round() as u32
+        } else {
+            words.next()?.parse().ok()?
+        };
+        let topology = if version < 3 {
+            Topology::Square
+        } else {
+            Topology::from_name(words.next()?)?
+        };
+        let time = words.next()?.parse().ok()?;
+        let initials = words.next()?.to_owned();
+        let undos = match words.next() {
+            Some(undos) => undos.parse().ok()?,
+            None => 0,
+        };
+
+        if words.next().is_some() {
+            return None;
+        }
+
+        Some(Score {
+            config: (width, height, mines, topology),
+            time: time,
+            undos: undos,
+            initials: initials,
+        })
+    }
+}
+
+/// The high scores of every configuration.
+pub struct Scores {
+    /// The scores, fastest first.
+    scores: Vec<Score>,
+}
+
+impl Scores {
+    /// Get the path of the high-score file.
+    ///
+    /// This is in the home directory, or the current directory if there is none.
+    fn path() -> PathBuf {
+        let mut path = env::var_os("HOME").map(PathBuf::from).unwrap_or_default();
+        path.push(SCORES_FILE);
+        path
+    }
+
+    /// Load the high scores.
+    ///
+    /// A missing file means there are no scores yet, and malformed lines are skipped, so this
+    /// never fails.
+    pub fn load() -> Scores {
+        let mut scores: Vec<Score> = File::open(Scores::path()).map(|file| {
+            let mut lines = BufReader::new(file).lines().filter_map(|line| line.ok()).peekable();
+            let header = lines.peek().and_then(|line| {
+                let mut words = line.split_whitespace();
+                match (words.next(), words.next(), words.next()) {
+                    (Some(MAGIC), Some(version), None) => version.parse().ok(),
+                    _ => None,
+                }
+            });
+            let version = match header {
+                Some(version) => {
+                    lines.next();
+                    version
+                },
+                None => 1,
+            };
+
+            // Files written by a later version cannot be told apart from garbage.
+            if version > VERSION {
+                return Vec::new();
+            }
+
+            lines.filter_map(|line| Score::parse(&line, version)).collect()
+        }).unwrap_or_default();
+
+        scores.sort_by_key(|score| score.time);
+        Scores {
+            scores: scores,
+        }
+    }
+
+    /// Write the high scores to the high-score file.
+    pub fn save(&self) -> io::Result<()> {
+        let mut file = BufWriter::new(File::create(Scores::path())?);
+        writeln!(file, "{} {}", MAGIC, VERSION)?;
+        for score in self.scores.iter() {
+            let (width, height, mines, topology) = score.config;
+            writeln!(file, "{} {} {} {} {} {} {}", width, height, mines, topology.name(), score.time,
+                     score.initials, score.undos)?;
+        }
+
+        file.flush()
+    }
+
+    /// Get the best scores of a configuration, fastest first.
+    pub fn best(&self, config: Config) -> Vec<&Score> {
+        self.scores.iter().filter(|score| score.config == config).take(MAX_SCORES).collect()
+    }
+
+    /// Get every configuration with scores, ordered by size, number of mines and topology.
+    pub fn configs(&self) -> Vec<Config> {
+        let mut configs: Vec<Config> = self.scores.iter().map(|score| score.config).collect();
+        configs.sort();
+        configs.dedup();
+        configs
+    }
+
+    /// Would a time make it into the table of a configuration?
+    pub fn qualifies(&self, config: Config, time: u64) -> bool {
+        let best = self.best(config);
+        best.len() < MAX_SCORES || best.last().map_or(true, |score| time < score.time)
+    }
+
+    /// Insert a score, dropping whatever falls off the table of its configuration.
+    pub fn insert(&mut self, score: Score) {
+        let index = self.scores.iter().position(|s| s.time > score.time).unwrap_or(self.scores.len());
+        let config = score.config;
+        self.scores.insert(index, score);
+
+        let mut kept = 0;
+        self.scores.retain(|s| {
+            if s.config != config {
+                return true;
+            }
+
+            kept += 1;
+            kept <= MAX_SCORES
+        });
+    }
+
+    /// Write the table of a configuration, one line per score, ending every line with _eol_.
+    pub fn write_table<W: Write>(&self, to: &mut W, config: Config, eol: &str) -> io::Result<()> {
+        let (width, height, mines, topology) = config;
+        write!(to, "{}x{} {}, {} mines:{}", width, height, topology.name(), mines, eol)?;
+
+        let best = self.best(config);
+        if best.is_empty() {
+            write!(to, "    no scores yet.{}", eol)?;
+        }
+        for (rank, score) in best.iter().enumerate() {
+            write!(to, "    {:2}. {:3} {:>9}", rank + 1, score.initials, format_time(score.time))?;
+            if score.undos > 0 {
+                write!(to, " ({} undos)", score.undos)?;
+            }
+            write!(to, "{}", eol)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Format a time in milliseconds as seconds, with tenths.
+pub fn format_time(time: u64) -> String {
+    format!("{}.{}s", time / 1000, time % 1000 / 100)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn old_scores_are_converted() {
+        let old = Score::parse("16 16 6 41200 abc", 1).unwrap();
+        assert_eq!(old.config, (16, 16, 43, Topology::Square));
+        assert_eq!((old.time, old.undos, &old.initials[..]), (41200, 0, "abc"));
+        assert!(Score::parse("16 16 0 41200 abc", 1).is_none());
+
+        let square = Score::parse("16 16 6 41200 abc 2", 2).unwrap();
+        assert_eq!(square.config, (16, 16, 6, Topology::Square));
+        assert_eq!(square.undos, 2);
+
+        let hex = Score::parse("16 16 6 hex 41200 abc 2", 3).unwrap();
+        assert_eq!(hex.config, (16, 16, 6, Topology::Hex));
+        assert!(Score::parse("16 16 6 41200 abc 2", 3).is_none());
+    }
+
+    #[test]
+    fn topologies_keep_apart() {
+        let mut scores = Scores {
+            scores: Vec::new(),
+        };
+        for (i, &topology) in [Topology::Square, Topology::Torus, Topology::Hex].iter().enumerate() {
+            scores.insert(Score {
+                config: (9, 9, 10, topology),
+                time: 1000 * (3 - i as u64),
+                undos: 0,
+                initials: "abc".to_owned(),
+            });
+        }
+
+        assert_eq!(scores.configs().len(), 3);
+        assert_eq!(scores.best((9, 9, 10, Topology::Torus))[0].time, 2000);
+    }
+}
@@ -0,0 +1,746 @@
+//! A deterministic, logical minesweeper solver.
+//!
+//! The solver plays a board like a careful player would: it only ever reveals cells that are
+//! provably safe. It is used to check that a generated board can be cleared without guessing.
+//!
+//! The board is described by two slices indexed by cell position: the neighbours of every cell
+//! and whether the cell holds a mine. The mine layout is only consulted when the solver reveals a
+//! cell, to read off its value, exactly like a player would.
+//!
+//! The same machinery gives the exact mine probabilities of a board as seen by the player, which
+//! is what hints are made of.
+
+/// The maximal number of cells in a frontier region that we are willing to enumerate.
+///
+/// Enumeration is exponential in the worst case, so larger regions are left to the cheaper rules.
+const MAX_ENUMERATION: usize = 24;
+
+/// What the solver knows about a cell.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Knowledge {
+    /// Nothing is known yet.
+    Unknown,
+    /// The cell is known to be free of mines.
+    Safe,
+    /// The cell is known to hold a mine.
+    Mine,
+}
+
+/// A constraint derived from a revealed number.
+///
+/// Exactly `mines` of the (unknown) cells in `cells` hold a mine.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Constraint {
+    /// The unknown cells, in increasing order.
+    pub cells: Vec<usize>,
+    /// The number of mines among them.
+    pub mines: usize,
+}
+
+impl Constraint {
+    /// Is every cell of this constraint also part of `other`?
+    fn is_subset(&self, other: &Constraint) -> bool {
+        self.cells.iter().all(|c| other.cells.binary_search(c).is_ok())
+    }
+
+    /// Push the trivially determined cells of this constraint.
+    ///
+    /// If no mines are left, all the cells are safe. If there are exactly as many mines as
+    /// cells, all of them are mines.
+    fn trivial(&self, safe: &mut Vec<usize>, mines: &mut Vec<usize>) {
+        if self.mines == 0 {
+            safe.extend_from_slice(&self.cells);
+        } else if self.mines == self.cells.len() {
+            mines.extend_from_slice(&self.cells);
+        }
+    }
+}
+
+/// The solver state.
+pub struct Solver<'a> {
+    /// The neighbours of every cell.
+    neighbours: &'a [Vec<usize>],
+    /// The mine layout.
+    mines: &'a [bool],
+    /// What the solver knows about each cell.
+    knowledge: Vec<Knowledge>,
+    /// The cells revealed by the solver.
+    revealed: Vec<bool>,
+}
+
+impl<'a> Solver<'a> {
+    /// Create a solver for a board, where nothing is revealed.
+    pub fn new(neighbours: &'a [Vec<usize>], mines: &'a [bool]) -> Solver<'a> {
+        Solver {
+            neighbours: neighbours,
+            mines: mines,
+            knowledge: vec![Knowledge::Unknown; mines.len()],
+            revealed: vec![false; mines.len()],
+        }
+    }
+
+    /// Play the board, starting by revealing `start`.
+    ///
+    /// Returns true if every free cell could be revealed without guessing.
+    pub fn solve(&mut self, start: usize) -> bool {
+        self.reveal(start);
+        while self.step() {}
+
+        self.is_solved()
+    }
+
+    /// Is every free cell revealed?
+    pub fn is_solved(&self) -> bool {
+        self.mines.iter().zip(self.revealed.iter()).all(|(&mine, &revealed)| mine || revealed)
+    }
+
+    /// Is the cell revealed?
+    pub fn is_revealed(&self, c: usize) -> bool {
+        self.revealed[c]
+    }
+
+    /// Find a mine which blocks the solver from making progress.
+    ///
+    /// This prefers mines next to the revealed area that could not be deduced, and otherwise any
+    /// mine next to the revealed area.
+    pub fn stuck_mine(&self) -> Option<usize> {
+        let border = |c: &usize| {
+            self.mines[*c] && !self.revealed[*c]
+                && self.neighbours[*c].iter().any(|&n| self.revealed[n])
+        };
+
+        (0..self.mines.len())
+            .filter(&border)
+            .find(|&c| self.knowledge[c] == Knowledge::Unknown)
+            .or_else(|| (0..self.mines.len()).find(&border))
+    }
+
+    /// Get the value of a cell, i.e. the number of adjacent mines.
+    fn value(&self, c: usize) -> usize {
+        self.neighbours[c].iter().filter(|&&n| self.mines[n]).count()
+    }
+
+    /// Reveal a safe cell, flood filling the free cells around it.
+    fn reveal(&mut self, c: usize) {
+        let mut stack = vec![c];
+
+        while let Some(c) = stack.pop() {
+            if self.revealed[c] {
+                continue;
+            }
+
+            self.revealed[c] = true;
+            self.knowledge[c] = Knowledge::Safe;
+
+            if self.value(c) == 0 {
+                stack.extend(self.neighbours[c].iter().filter(|&&n| !self.revealed[n]));
+            }
+        }
+    }
+
+    /// Derive the constraints given by the revealed numbers bordering unknown cells.
+    pub fn constraints(&self) -> Vec<Constraint> {
+        let mut constraints = Vec::new();
+
+        for c in (0..self.revealed.len()).filter(|&c| self.revealed[c]) {
+            let mut cells = Vec::new();
+            let mut known = 0;
+
+            for &n in self.neighbours[c].iter() {
+                match self.knowledge[n] {
+                    Knowledge::Unknown => cells.push(n),
+                    Knowledge::Mine => known += 1,
+                    Knowledge::Safe => {},
+                }
+            }
+
+            if !cells.is_empty() {
+                cells.sort();
+                cells.dedup();
+                constraints.push(Constraint {
+                    cells: cells,
+                    mines: self.value(c).saturating_sub(known),
+                });
+            }
+        }
+
+        constraints.sort_by(|a, b| a.cells.cmp(&b.cells));
+        constraints.dedup();
+        constraints
+    }
+
+    /// Make one round of deductions, and act on them.
+    ///
+    /// The rules are tried from the cheapest to the most expensive: single-cell deductions, the
+    /// subset rule, and finally full enumeration of the frontier. Returns false if nothing new
+    /// could be deduced.
+    fn step(&mut self) -> bool {
+        let constraints = self.constraints();
+        let mut safe = Vec::new();
+        let mut mines = Vec::new();
+
+        for constraint in constraints.iter() {
+            constraint.trivial(&mut safe, &mut mines);
+        }
+
+        if safe.is_empty() && mines.is_empty() {
+            subset_rule(&constraints, &mut safe, &mut mines);
+        }
+
+        if safe.is_empty() && mines.is_empty() {
+            enumerate_frontier(&constraints, &mut safe, &mut mines);
+        }
+
+        for &c in mines.iter() {
+            self.knowledge[c] = Knowledge::Mine;
+        }
+        for &c in safe.iter() {
+            self.reveal(c);
+        }
+
+        !(safe.is_empty() && mines.is_empty())
+    }
+}
+
+/// Apply the subset rule.
+///
+/// If the cells of constraint _A_ are a subset of those of constraint _B_, then the cells of _B_
+/// not in _A_ hold exactly the difference of their mine counts.
+fn subset_rule(constraints: &[Constraint], safe: &mut Vec<usize>, mines: &mut Vec<usize>) {
+    for a in constraints.iter() {
+        for b in constraints.iter() {
+            if a.cells.len() < b.cells.len() && a.is_subset(b) {
+                Constraint {
+                    cells: b.cells.iter().cloned().filter(|c| a.cells.binary_search(c).is_err()).collect(),
+                    mines: b.mines.saturating_sub(a.mines),
+                }.trivial(safe, mines);
+            }
+        }
+    }
+}
+
+/// Split the frontier into independent regions.
+///
+/// Two cells are in the same region if they share a constraint. Each region is returned as its
+/// cells, and the indices of its constraints.
+pub fn regions(constraints: &[Constraint]) -> Vec<(Vec<usize>, Vec<usize>)> {
+    let mut regions: Vec<(Vec<usize>, Vec<usize>)> = Vec::new();
+
+    for (i, constraint) in constraints.iter().enumerate() {
+        // Merge every region sharing a cell with this constraint.
+        let mut merged = (constraint.cells.clone(), vec![i]);
+        let mut j = 0;
+        while j < regions.len() {
+            if regions[j].0.iter().any(|c| constraint.cells.binary_search(c).is_ok()) {
+                let (cells, indices) = regions.swap_remove(j);
+                merged.0.extend(cells);
+                merged.1.extend(indices);
+            } else {
+                j += 1;
+            }
+        }
+
+        merged.0.sort();
+        merged.0.dedup();
+        regions.push(merged);
+    }
+
+    regions
+}
+
+/// The solutions of a region with a given number of mines.
+#[derive(Clone, Debug)]
+struct Tally {
+    /// The number of solutions.
+    solutions: u64,
+    /// For every cell of the region, the number of solutions in which it holds a mine.
+    counts: Vec<u64>,
+}
+
+/// Enumerate every mine assignment of a region satisfying its constraints.
+///
+/// The solutions are tallied by their number of mines, which is the index in the returned
+/// vector.
+fn enumerate(cells: &[usize], constraints: &[&Constraint]) -> Vec<Tally> {
+    // For every cell, the constraints it takes part in.
+    let members: Vec<Vec<usize>> = cells.iter().map(|c| {
+        (0..constraints.len()).filter(|&i| constraints[i].cells.binary_search(c).is_ok()).collect()
+    }).collect();
+
+    let mut state = Enumeration {
+        members: &members,
+        // The mines yet to be placed, and the cells yet to be assigned, of every constraint.
+        left: constraints.iter().map(|c| c.mines).collect(),
+        open: constraints.iter().map(|c| c.cells.len()).collect(),
+        assignment: vec![false; cells.len()],
+        tallies: Vec::new(),
+    };
+    state.search(0);
+
+    state.tallies
+}
+
+/// The state of a backtracking enumeration.
+struct Enumeration<'a> {
+    members: &'a [Vec<usize>],
+    left: Vec<usize>,
+    open: Vec<usize>,
+    assignment: Vec<bool>,
+    tallies: Vec<Tally>,
+}
+
+impl<'a> Enumeration<'a> {
+    /// Assign the cells from `i` onwards.
+    fn search(&mut self, i: usize) {
+        if i == self.assignment.len() {
+            let mines = self.assignment.iter().filter(|&&mine| mine).count();
+            while self.tallies.len() <= mines {
+                self.tallies.push(Tally {
+                    solutions: 0,
+                    counts: vec![0; self.assignment.len()],
+                });
+            }
+
+            let tally = &mut self.tallies[mines];
+            tally.solutions += 1;
+            for (count, &mine) in tally.counts.iter_mut().zip(self.assignment.iter()) {
+                *count += mine as u64;
+            }
+            return;
+        }
+
+        for &mine in [false, true].iter() {
+            // Check that the choice leaves every constraint of the cell satisfiable.
+            let feasible = self.members[i].iter().all(|&k| {
+                if mine {
+                    self.left[k] > 0
+                } else {
+                    self.left[k] < self.open[k]
+                }
+            });
+
+            if feasible {
+                for &k in self.members[i].iter() {
+                    self.open[k] -= 1;
+                    self.left[k] -= mine as usize;
+                }
+                self.assignment[i] = mine;
+
+                self.search(i + 1);
+
+                for &k in self.members[i].iter() {
+                    self.open[k] += 1;
+                    self.left[k] += mine as usize;
+                }
+            }
+        }
+
+        self.assignment[i] = false;
+    }
+}
+
+/// Enumerate the small frontier regions.
+///
+/// A cell holding a mine in every solution of its region is a mine, and one holding a mine in
+/// none of them is safe.
+fn enumerate_frontier(constraints: &[Constraint], safe: &mut Vec<usize>, mines: &mut Vec<usize>) {
+    for (cells, indices) in regions(constraints) {
+        if cells.len() > MAX_ENUMERATION {
+            continue;
+        }
+
+        let region: Vec<&Constraint> = indices.iter().map(|&i| &constraints[i]).collect();
+
+        // Without a mine count to go by, it does not matter how many mines a solution has.
+        let mut solutions = 0;
+        let mut counts = vec![0; cells.len()];
+        for tally in enumerate(&cells, &region) {
+            solutions += tally.solutions;
+            for (count, &n) in counts.iter_mut().zip(tally.counts.iter()) {
+                *count += n;
+            }
+        }
+
+        if solutions == 0 {
+            continue;
+        }
+
+        for (&c, &count) in cells.iter().zip(counts.iter()) {
+            if count == 0 {
+                safe.push(c);
+            } else if count == solutions {
+                mines.push(c);
+            }
+        }
+    }
+}
+
+/// What the player sees of a cell.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum View {
+    /// A concealed cell.
+    Concealed,
+    /// A concealed cell with a flag on it.
+    Flagged,
+    /// A revealed cell, with its value.
+    Revealed(u8),
+}
+
+/// Calculate the probability of every concealed cell to hold a mine.
+///
+/// This only uses what the player can see: the revealed values, the flags (which are trusted,
+/// unless they contradict the values) and the total number of mines. Every mine layout agreeing
+/// with those is considered equally likely. Revealed and flagged cells get `None`.
+///
+/// The probabilities are exact, except on frontier regions too large to enumerate, whose cells
+/// are counted as if they were away from the frontier.
+pub fn probabilities(neighbours: &[Vec<usize>], view: &[View], mines: usize) -> Vec<Option<f64>> {
+    probabilities_given(neighbours, view, mines, true)
+        .or_else(|| probabilities_given(neighbours, view, mines, false))
+        .unwrap_or_else(|| view.iter().map(|&cell| match cell {
+            View::Concealed => Some(0.5),
+            _ => None,
+        }).collect())
+}
+
+/// Calculate the probabilities, either trusting the flags, or treating them as concealed cells.
+///
+/// Returns `None` if no mine layout agrees with the view.
+fn probabilities_given(neighbours: &[Vec<usize>], view: &[View], mines: usize, flags: bool)
+                       -> Option<Vec<Option<f64>>> {
+    let known = |c: usize| flags && view[c] == View::Flagged;
+    let concealed = |c: usize| match view[c] {
+        View::Concealed => true,
+        View::Flagged => !flags,
+        View::Revealed(_) => false,
+    };
+
+    // Derive the constraints of the revealed values.
+    let mut constraints = Vec::new();
+    for (c, &cell) in view.iter().enumerate() {
+        if let View::Revealed(value) = cell {
+            let mut cells: Vec<usize> = neighbours[c].iter().cloned().filter(|&n| concealed(n)).collect();
+            let flagged = neighbours[c].iter().filter(|&&n| known(n)).count();
+
+            if flagged > value as usize {
+                return None;
+            }
+            if !cells.is_empty() {
+                cells.sort();
+                cells.dedup();
+                constraints.push(Constraint {
+                    cells: cells,
+                    mines: value as usize - flagged,
+                });
+            }
+        }
+    }
+    constraints.sort_by(|a, b| a.cells.cmp(&b.cells));
+    constraints.dedup();
+
+    // The mines left to place among the concealed cells.
+    let placed = (0..view.len()).filter(|&c| known(c)).count();
+    let left = mines.checked_sub(placed)?;
+
+    // Enumerate the frontier regions. For every region, the weights of its solutions by their
+    // number of mines, scaled so that the largest is 1 (which does not change the odds).
+    let mut enumerated = Vec::new();
+    let mut frontier = vec![false; view.len()];
+    for (cells, indices) in regions(&constraints) {
+        if cells.len() > MAX_ENUMERATION {
+            continue;
+        }
+
+        let region: Vec<&Constraint> = indices.iter().map(|&i| &constraints[i]).collect();
+        let tallies = enumerate(&cells, &region);
+        let max = tallies.iter().map(|tally| tally.solutions).max().unwrap_or(0);
+        if max == 0 {
+            return None;
+        }
+
+        for &c in cells.iter() {
+            frontier[c] = true;
+        }
+        let weights = tallies.iter().map(|tally| tally.solutions as f64 / max as f64).collect();
+        enumerated.push((cells, tallies, weights));
+    }
+
+    // The concealed cells away from the (enumerated) frontier.
+    let interior = (0..view.len()).filter(|&c| concealed(c) && !frontier[c]).count();
+
+    // The weight of having _s_ mines on the frontier is the number of ways to place the
+    // remaining mines in the interior, that is the binomial coefficient (interior, left - s).
+    // These get huge, so we work with their logarithms relative to the first one.
+    let max_frontier: usize = enumerated.iter().map(|&(_, ref tallies, _)| tallies.len() - 1).sum();
+    let mut log_binomial = vec![None; max_frontier + 1];
+    {
+        let mut log = 0.0;
+        let mut first = true;
+        for s in 0..max_frontier + 1 {
+            if s > left || left - s > interior {
+                continue;
+            }
+
+            // C(n, k - 1) / C(n, k) = k / (n - k + 1), for k = left - s + 1.
+            if !first {
+                let k = (left - s + 1) as f64;
+                log += (k / (interior as f64 - k + 1.0)).ln();
+            }
+            first = false;
+            log_binomial[s] = Some(log);
+        }
+    }
+    let max_log = log_binomial.iter().filter_map(|&log| log).fold(None, |max: Option<f64>, log| {
+        Some(max.map_or(log, |max| max.max(log)))
+    })?;
+    let interior_weight: Vec<f64> = log_binomial.iter().map(|&log| {
+        log.map_or(0.0, |log| (log - max_log).exp())
+    }).collect();
+
+    // Combine the regions, by convolving their weights.
+    let convolve = |skip: Option<usize>| {
+        let mut total = vec![1.0];
+        for (i, &(_, _, ref weights)) in enumerated.iter().enumerate() {
+            if Some(i) == skip {
+                continue;
+            }
+
+            let weights: &Vec<f64> = weights;
+            let mut next = vec![0.0; total.len() + weights.len() - 1];
+            for (a, &x) in total.iter().enumerate() {
+                for (b, &y) in weights.iter().enumerate() {
+                    next[a + b] += x * y;
+                }
+            }
+
+            // Keep the numbers in range.
+            let max = next.iter().cloned().fold(0.0, f64::max);
+            if max > 0.0 {
+                for x in next.iter_mut() {
+                    *x /= max;
+                }
+            }
+            total = next;
+        }
+        total
+    };
+
+    let all = convolve(None);
+    let total: f64 = all.iter().enumerate().map(|(s, &w)| w * interior_weight[s]).sum();
+    if total == 0.0 {
+        return None;
+    }
+
+    let mut probabilities = vec![None; view.len()];
+
+    // The frontier cells.
+    for (i, &(ref cells, ref tallies, ref weights)) in enumerated.iter().enumerate() {
+        let others = convolve(Some(i));
+
+        // The weight of the whole board with _k_ mines in this region, and the share of the
+        // solutions with a mine on each cell.
+        let mut sum = 0.0;
+        let mut counts = vec![0.0; cells.len()];
+        for (k, tally) in tallies.iter().enumerate() {
+            if tally.solutions == 0 {
+                continue;
+            }
+
+            let weight: f64 = others.iter().enumerate().map(|(s, &w)| w * interior_weight[s + k]).sum();
+            let weight = weight * weights[k] / tally.solutions as f64;
+            sum += weight * tally.solutions as f64;
+            for (count, &n) in counts.iter_mut().zip(tally.counts.iter()) {
+                *count += weight * n as f64;
+            }
+        }
+
+        if sum == 0.0 {
+            return None;
+        }
+        for (&c, &count) in cells.iter().zip(counts.iter()) {
+            probabilities[c] = Some(count / sum);
+        }
+    }
+
+    // The interior cells all share the expected number of mines left for the interior.
+    if interior > 0 {
+        let expected: f64 = all.iter().enumerate().map(|(s, &w)| {
+            w * interior_weight[s] * left.saturating_sub(s) as f64
+        }).sum::<f64>() / total;
+
+        for c in (0..view.len()).filter(|&c| concealed(c) && !frontier[c]) {
+            probabilities[c] = Some(expected / interior as f64);
+        }
+    }
+
+    Some(probabilities)
+}
+
+/// Pick the concealed cell least likely to hold a mine.
+///
+/// Cells next to revealed ones are preferred among equally likely cells, since revealing them
+/// tells more. Returns the cell and its probability, or `None` if every cell is revealed or
+/// flagged.
+pub fn hint(neighbours: &[Vec<usize>], view: &[View], mines: usize) -> Option<(usize, f64)> {
+    let next_to_revealed = |c: usize| neighbours[c].iter().any(|&n| match view[n] {
+        View::Revealed(_) => true,
+        _ => false,
+    });
+
+    probabilities(neighbours, view, mines).into_iter().enumerate()
+        .filter_map(|(c, p)| p.map(|p| (c, p)))
+        .fold(None, |best: Option<(usize, f64)>, (c, p)| match best {
+            Some((b, q)) if q < p || (q == p && (next_to_revealed(b) || !next_to_revealed(c))) => {
+                Some((b, q))
+            },
+            _ => Some((c, p)),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use topology::Topology;
+
+    /// Get the neighbours of every cell of a single row of _len_ cells.
+    fn row(len: usize) -> Vec<Vec<usize>> {
+        (0..len).map(|c| {
+            let mut neighbours = Vec::new();
+            if c > 0 {
+                neighbours.push(c - 1);
+            }
+            if c + 1 < len {
+                neighbours.push(c + 1);
+            }
+            neighbours
+        }).collect()
+    }
+
+    /// Get the neighbours and the mines of a square grid from a picture of its rows, where `*`
+    /// is a mine.
+    fn grid(rows: &[&str]) -> (Vec<Vec<usize>>, Vec<bool>) {
+        let (width, height) = (rows[0].len() as u16, rows.len() as u16);
+        let mut neighbours = Vec::new();
+        for y in 0..height {
+            for x in 0..width {
+                neighbours.push(Topology::Square.neighbours(width, height, x, y).into_iter()
+                    .map(|(x, y)| y as usize * width as usize + x as usize)
+                    .collect());
+            }
+        }
+
+        (neighbours, rows.iter().flat_map(|row| row.chars()).map(|c| c == '*').collect())
+    }
+
+    /// Is _p_ a probability close enough to _expected_?
+    fn close(p: Option<f64>, expected: f64) -> bool {
+        p.map_or(false, |p| (p - expected).abs() < 1e-9)
+    }
+
+    #[test]
+    fn subset_of_a_constraint() {
+        let a = Constraint {
+            cells: vec![1, 2],
+            mines: 1,
+        };
+        let b = Constraint {
+            cells: vec![1, 2, 3],
+            mines: 1,
+        };
+        let c = Constraint {
+            cells: vec![2, 3, 4],
+            mines: 2,
+        };
+        let (mut safe, mut mines) = (Vec::new(), Vec::new());
+
+        subset_rule(&[a.clone(), b], &mut safe, &mut mines);
+        assert_eq!((&safe[..], &mines[..]), (&[3][..], &[][..]));
+
+        // The difference of overlapping constraints says nothing.
+        safe.clear();
+        subset_rule(&[a, c], &mut safe, &mut mines);
+        assert!(safe.is_empty() && mines.is_empty());
+    }
+
+    #[test]
+    fn one_two_one_is_solved() {
+        let (neighbours, mines) = grid(&["....", "....", "*.*."]);
+        let mut solver = Solver::new(&neighbours, &mines);
+
+        // The first click opens the two top rows, reading 1 2 1 1 above the mines. None of them
+        // tells anything on its own, but the subset rule does.
+        solver.reveal(0);
+        let constraints = solver.constraints();
+        let (mut safe, mut found) = (Vec::new(), Vec::new());
+        for constraint in constraints.iter() {
+            constraint.trivial(&mut safe, &mut found);
+        }
+        assert!(safe.is_empty() && found.is_empty());
+
+        subset_rule(&constraints, &mut safe, &mut found);
+        assert_eq!((&safe[..], &found[..]), (&[9][..], &[10][..]));
+
+        assert!(solver.solve(0));
+        assert!(solver.is_solved());
+    }
+
+    #[test]
+    fn a_guess_is_not_solved() {
+        // The corners behind the pair of mines read the same whether the mine next to them is
+        // the lower one of the pair, or both corners hold a mine instead. Without counting the
+        // mines, there is no telling which.
+        let (neighbours, mines) = grid(&["....", "..**", "...."]);
+        let mut solver = Solver::new(&neighbours, &mines);
+
+        assert!(!solver.solve(0));
+        assert!(!solver.is_revealed(3) && !solver.is_revealed(11));
+        assert_eq!(solver.stuck_mine(), Some(7));
+    }
+
+    #[test]
+    fn next_to_a_zero_is_safe() {
+        use self::View::*;
+        let view = [Revealed(0), Concealed, Concealed];
+
+        let probabilities = probabilities(&row(3), &view, 1);
+        assert_eq!(probabilities[0], None);
+        assert_eq!(probabilities[1], Some(0.0));
+        assert_eq!(probabilities[2], Some(1.0));
+        assert_eq!(hint(&row(3), &view, 1), Some((1, 0.0)));
+    }
+
+    #[test]
+    fn one_mine_between_two_cells_is_a_coin_toss() {
+        use self::View::*;
+        let view = [Concealed, Revealed(1), Concealed];
+
+        let probabilities = probabilities(&row(3), &view, 1);
+        assert!(close(probabilities[0], 0.5));
+        assert!(close(probabilities[2], 0.5));
+    }
+
+    #[test]
+    fn mines_left_spread_over_the_unknown_cells() {
+        use self::View::*;
+        // The mine next to the 1 is on either side of it, so the other mines are somewhere among
+        // the last three cells, which nothing is known about.
+        let view = [Concealed, Revealed(1), Concealed, Concealed, Concealed, Concealed];
+
+        for &(mines, expected) in &[(1, 0.0), (2, 1.0 / 3.0), (3, 2.0 / 3.0), (4, 1.0)] {
+            let probabilities = probabilities(&row(6), &view, mines);
+            assert!(close(probabilities[0], 0.5));
+            assert!(close(probabilities[2], 0.5));
+            for c in 3..6 {
+                assert!(close(probabilities[c], expected), "{} mines: {:?}", mines, probabilities);
+            }
+        }
+    }
+
+    #[test]
+    fn flags_are_taken_as_mines() {
+        use self::View::*;
+        let view = [Flagged, Revealed(1), Concealed];
+
+        assert_eq!(probabilities(&row(3), &view, 1)[2], Some(0.0));
+    }
+}
@@ -0,0 +1,133 @@
+//! The shapes of the board.
+//!
+//! The topology decides which cells are adjacent to each other, and where cells are drawn on the
+//! screen. Everything counting or visiting neighbours goes through it.
+
+/// A board topology.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Topology {
+    /// The classic square grid, bounded by its edges.
+    Square,
+    /// A square grid wrapping around at every edge, so that numbers count across them.
+    Torus,
+    /// A grid of hexagons, where every other row is shifted half a cell to the right.
+    Hex,
+}
+
+impl Topology {
+    /// Parse the name of a topology.
+    pub fn from_name(name: &str) -> Option<Topology> {
+        match name {
+            "square" => Some(Topology::Square),
+            "torus" => Some(Topology::Torus),
+            "hex" => Some(Topology::Hex),
+            _ => None,
+        }
+    }
+
+    /// Get the name of the topology.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Topology::Square => "square",
+            Topology::Torus => "torus",
+            Topology::Hex => "hex",
+        }
+    }
+
+    /// Calculate the cells adjacent to (x, y), on a grid of the given size.
+    ///
+    /// Every neighbour is listed once, even on grids so small that wrapping around would reach
+    /// the same cell from several directions.
+    pub fn neighbours(&self, width: u16, height: u16, x: u16, y: u16) -> Vec<(u16, u16)> {
+        let offsets: &[(i32, i32)] = match *self {
+            Topology::Square | Topology::Torus => &[
+                (-1, -1), (0, -1), (1, -1),
+                (-1, 0),           (1, 0),
+                (-1, 1),  (0, 1),  (1, 1),
+            ],
+            // Odd rows are shifted to the right, so the rows above and below are reached from
+            // one column further to the right.
+            Topology::Hex if y % 2 == 0 => &[
+                (-1, -1), (0, -1),
+                (-1, 0),  (1, 0),
+                (-1, 1),  (0, 1),
+            ],
+            Topology::Hex => &[
+                (0, -1), (1, -1),
+                (-1, 0), (1, 0),
+                (0, 1),  (1, 1),
+            ],
+        };
+
+        let (w, h) = (width as i32, height as i32);
+        let mut neighbours = Vec::with_capacity(offsets.len());
+
+        for &(dx, dy) in offsets {
+            let (mut nx, mut ny) = (x as i32 + dx, y as i32 + dy);
+
+            if *self == Topology::Torus {
+                nx = (nx + w) % w;
+                ny = (ny + h) % h;
+            } else if nx < 0 || ny < 0 || nx >= w || ny >= h {
+                continue;
+            }
+
+            let n = (nx as u16, ny as u16);
+            if n != (x, y) && !neighbours.contains(&n) {
+                neighbours.push(n);
+            }
+        }
+
+        neighbours
+    }
+
+    /// Get the terminal position (as given to `cursor::Goto`) of the cell at (x, y).
+    ///
+    /// The grid is framed, so the first cell is at (2, 2).
+    pub fn screen(&self, x: u16, y: u16) -> (u16, u16) {
+        match *self {
+            Topology::Square | Topology::Torus => (x + 2, y + 2),
+            // Hexagons take two columns, and odd rows are shifted by one.
+            Topology::Hex => (2 * x + y % 2 + 2, y + 2),
+        }
+    }
+
+    /// Get the cell drawn at a terminal position, if any.
+    ///
+    /// This is the inverse of `screen`, and gives `None` for the frame and the gaps between
+    /// hexagons.
+    pub fn cell_at(&self, width: u16, height: u16, col: u16, row: u16) -> Option<(u16, u16)> {
+        if col < 2 || row < 2 || row - 2 >= height {
+            return None;
+        }
+
+        let (col, y) = (col - 2, row - 2);
+        let x = match *self {
+            Topology::Square | Topology::Torus => col,
+            Topology::Hex if col < y % 2 || (col - y % 2) % 2 != 0 => return None,
+            Topology::Hex => (col - y % 2) / 2,
+        };
+
+        if x < width {
+            Some((x, y))
+        } else {
+            None
+        }
+    }
+
+    /// Get the number of columns inside the frame, for a grid of the given width.
+    pub fn columns(&self, width: u16) -> u16 {
+        match *self {
+            Topology::Square | Topology::Torus => width,
+            Topology::Hex => 2 * width + 1,
+        }
+    }
+
+    /// Get the widest grid which fits in the given number of columns inside the frame.
+    pub fn fit(&self, columns: u16) -> u16 {
+        match *self {
+            Topology::Square | Topology::Torus => columns,
+            Topology::Hex => columns.saturating_sub(1) / 2,
+        }
+    }
+}
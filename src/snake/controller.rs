@@ -0,0 +1,279 @@
+//! Snake controllers.
+//!
+//! A controller steers a snake, picking the direction to turn it to every step. Players steer
+//! through the keyboard, and bots by looking at the world.
+
+use std::collections::VecDeque;
+
+use map::Map;
+use world::{BodyPart, Direction, World, DIRECTIONS};
+
+/// The number of turns a player can queue up ahead.
+const MAX_QUEUED: usize = 3;
+
+/// The names of every controller.
+pub const NAMES: [&'static str; 4] = ["human", "greedy", "bfs", "hamilton"];
+
+/// Something steering a snake.
+pub trait SnakeController {
+    /// Pick the direction to turn a snake to, if any, given the world it is in.
+    fn control(&mut self, world: &World, snake: usize) -> Option<Direction>;
+
+    /// A key steering the snake was pressed.
+    ///
+    /// Bots pay no attention to the keyboard.
+    fn key(&mut self, _direction: Direction) {}
+}
+
+/// Get a controller by its name, for playing on the given map.
+pub fn from_name(name: &str, map: &Map) -> Result<Box<dyn SnakeController>, &'static str> {
+    match name {
+        "human" => Ok(Box::new(Human { turns: VecDeque::new() })),
+        "greedy" => Ok(Box::new(Greedy)),
+        "bfs" => Ok(Box::new(Bfs)),
+        "hamilton" => match Hamilton::new(map) {
+            Some(bot) => Ok(Box::new(bot)),
+            None => Err("the hamilton bot needs a rectangular play area without inner walls, with \
+                         an even width or height"),
+        },
+        _ => Err("unknown bot"),
+    }
+}
+
+/// A player at the keyboard.
+///
+/// Keys pressed in quick succession are queued up, and make a turn each step.
+pub struct Human {
+    /// The turns still to make.
+    turns: VecDeque<Direction>,
+}
+
+impl SnakeController for Human {
+    fn control(&mut self, world: &World, snake: usize) -> Option<Direction> {
+        let current = world.snakes[snake].direction;
+
+        // Skip the turns which would not change anything, so they do not waste a step.
+        while let Some(direction) = self.turns.pop_front() {
+            if direction != current && direction != current.opposite() {
+                return Some(direction);
+            }
+        }
+
+        None
+    }
+
+    fn key(&mut self, direction: Direction) {
+        if self.turns.len() < MAX_QUEUED && self.turns.back() != Some(&direction) {
+            self.turns.push_back(direction);
+        }
+    }
+}
+
+/// A bot heading straight for the food, as long as it does not run into anything right away.
+pub struct Greedy;
+
+impl SnakeController for Greedy {
+    fn control(&mut self, world: &World, snake: usize) -> Option<Direction> {
+        let food = (world.food.x, world.food.y);
+
+        safe_moves(world, snake).into_iter()
+            .min_by_key(|&(_, cell)| distance(world, cell, food))
+            .map(|(direction, _)| direction)
+    }
+}
+
+/// A bot following the shortest path to the food.
+///
+/// When the food cannot be reached, it heads wherever there is the most room instead.
+pub struct Bfs;
+
+impl SnakeController for Bfs {
+    fn control(&mut self, world: &World, snake: usize) -> Option<Direction> {
+        let moves = safe_moves(world, snake);
+        let food = (world.food.x, world.food.y);
+        let width = world.map.width as usize;
+
+        // Search outwards from the head, remembering the first move on the way to every cell.
+        let mut first = vec![None; width * world.map.height as usize];
+        let mut queue = VecDeque::new();
+        for &(direction, (x, y)) in moves.iter() {
+            first[y as usize * width + x as usize] = Some(direction);
+            queue.push_back((x, y));
+        }
+
+        while let Some((x, y)) = queue.pop_front() {
+            let direction = first[y as usize * width + x as usize];
+            if (x, y) == food {
+                return direction;
+            }
+
+            for &next in DIRECTIONS.iter() {
+                if let Some((x, y)) = world.neighbour(x, y, next) {
+                    if first[y as usize * width + x as usize].is_none() && world.is_free(x, y) {
+                        first[y as usize * width + x as usize] = direction;
+                        queue.push_back((x, y));
+                    }
+                }
+            }
+        }
+
+        moves.into_iter()
+            .max_by_key(|&(_, cell)| room(world, cell))
+            .map(|(direction, _)| direction)
+    }
+}
+
+/// A bot going round a fixed cycle through every cell of the play area.
+///
+/// It takes its time, but since the snake never fills the whole cycle, it never runs into itself.
+pub struct Hamilton {
+    /// The cells of the cycle, in order.
+    cycle: Vec<(u16, u16)>,
+    /// The position of every cell of the map in the cycle.
+    position: Vec<usize>,
+    /// The width of the map.
+    width: u16,
+}
+
+impl Hamilton {
+    /// Lay a cycle through every free cell of a map.
+    ///
+    /// The free cells need to form a rectangle with an even width or height. Returns None if
+    /// they do not.
+    pub fn new(map: &Map) -> Option<Hamilton> {
+        let free: Vec<(u16, u16)> = (0..map.height)
+            .flat_map(|y| (0..map.width).map(move |x| (x, y)))
+            .filter(|&(x, y)| !map.is_wall(x, y))
+            .collect();
+
+        // The first and last free cells are the corners of the rectangle, if there is one.
+        let (x0, y0) = *free.first()?;
+        let (x1, y1) = *free.last()?;
+        if x1 < x0 || (y0..y1 + 1).any(|y| (x0..x1 + 1).any(|x| map.is_wall(x, y))) {
+            return None;
+        }
+
+        let (width, height) = (x1 + 1 - x0, y1 + 1 - y0);
+        if free.len() != width as usize * height as usize || width < 2 || height < 2 {
+            return None;
+        }
+
+        // Sweep the rows, leaving out the first column, which leads back to the start. The same
+        // goes for columns, if only the width is even.
+        let cycle: Vec<(u16, u16)> = if height % 2 == 0 {
+            sweep(width, height).into_iter().map(|(x, y)| (x0 + x, y0 + y)).collect()
+        } else if width % 2 == 0 {
+            sweep(height, width).into_iter().map(|(y, x)| (x0 + x, y0 + y)).collect()
+        } else {
+            return None;
+        };
+
+        let mut position = vec![0; map.width as usize * map.height as usize];
+        for (i, &(x, y)) in cycle.iter().enumerate() {
+            position[y as usize * map.width as usize + x as usize] = i;
+        }
+
+        Some(Hamilton {
+            cycle: cycle,
+            position: position,
+            width: map.width,
+        })
+    }
+}
+
+impl SnakeController for Hamilton {
+    fn control(&mut self, world: &World, snake: usize) -> Option<Direction> {
+        let snake = &world.snakes[snake];
+        let head = snake.head();
+        let i = self.position[head.y as usize * self.width as usize + head.x as usize];
+        let n = self.cycle.len();
+
+        // The cycle can be gone round either way, and the snake cannot turn back, so follow it
+        // the way the snake is already going.
+        let next = direction(head, self.cycle[(i + 1) % n]);
+        if next == snake.direction.opposite() {
+            Some(direction(head, self.cycle[(i + n - 1) % n]))
+        } else {
+            Some(next)
+        }
+    }
+}
+
+/// Sweep a grid back and forth along its rows, and back up along its first column.
+///
+/// The height has to be even, for the last row to end next to the first column.
+fn sweep(width: u16, height: u16) -> Vec<(u16, u16)> {
+    let mut cells: Vec<(u16, u16)> = (0..width).map(|x| (x, 0)).collect();
+
+    for y in 1..height {
+        if y % 2 == 1 {
+            cells.extend((1..width).rev().map(|x| (x, y)));
+        } else {
+            cells.extend((1..width).map(|x| (x, y)));
+        }
+    }
+
+    cells.extend((1..height).rev().map(|y| (0, y)));
+    cells
+}
+
+/// Get the direction from a body part to a cell next to it.
+fn direction(from: &BodyPart, (x, y): (u16, u16)) -> Direction {
+    if x > from.x {
+        Direction::Right
+    } else if x < from.x {
+        Direction::Left
+    } else if y > from.y {
+        Direction::Down
+    } else {
+        Direction::Up
+    }
+}
+
+/// Get the moves of a snake which do not run into anything right away, along with the cell they
+/// lead to.
+fn safe_moves(world: &World, snake: usize) -> Vec<(Direction, (u16, u16))> {
+    let snake = &world.snakes[snake];
+    let head = snake.head();
+
+    DIRECTIONS.iter()
+        .filter(|&&direction| direction != snake.direction.opposite())
+        .filter_map(|&direction| world.neighbour(head.x, head.y, direction).map(|cell| (direction, cell)))
+        .filter(|&(_, (x, y))| world.is_free(x, y))
+        .collect()
+}
+
+/// Get the number of steps between two cells, walls aside.
+fn distance(world: &World, (x0, y0): (u16, u16), (x1, y1): (u16, u16)) -> u16 {
+    let dx = if x0 > x1 { x0 - x1 } else { x1 - x0 };
+    let dy = if y0 > y1 { y0 - y1 } else { y1 - y0 };
+
+    if world.wrap {
+        dx.min(world.map.width - dx) + dy.min(world.map.height - dy)
+    } else {
+        dx + dy
+    }
+}
+
+/// Get the number of free cells reachable from a cell.
+fn room(world: &World, (x, y): (u16, u16)) -> usize {
+    let width = world.map.width as usize;
+    let mut seen = vec![false; width * world.map.height as usize];
+    let mut stack = vec![(x, y)];
+    seen[y as usize * width + x as usize] = true;
+
+    let mut count = 0;
+    while let Some((x, y)) = stack.pop() {
+        count += 1;
+        for &direction in DIRECTIONS.iter() {
+            if let Some((x, y)) = world.neighbour(x, y, direction) {
+                if !seen[y as usize * width + x as usize] && world.is_free(x, y) {
+                    seen[y as usize * width + x as usize] = true;
+                    stack.push((x, y));
+                }
+            }
+        }
+    }
+
+    count
+}
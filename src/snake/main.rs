@@ -2,29 +2,63 @@ extern crate termion;
 extern crate extra;
 
 use termion::{async_stdin, clear, color, cursor, style};
+use termion::event::Key;
+use termion::input::{Keys, TermRead};
 use termion::raw::IntoRawMode;
-use std::io::{stdout, stdin, Read, Write};
-use std::time::{Instant, Duration};
-use std::collections::VecDeque;
+use std::cmp::Ordering;
+use std::env;
+use std::fs::File;
+use std::io::{self, stdout, BufReader, Read, Write};
+use std::process;
+use std::time::{Instant, Duration, SystemTime, UNIX_EPOCH};
 use std::thread::sleep;
 use extra::rand::Randomizer;
 
+mod controller;
+mod map;
+mod scores;
+mod world;
+
+use controller::SnakeController;
+use map::Map;
+use scores::{Score, Scores};
+use world::{Direction, Event, FoodKind, World};
+
 mod graphics {
-    pub const TOP_LEFT_CORNER: &'static str = "╔";
-    pub const TOP_RIGHT_CORNER: &'static str = "╗";
-    pub const BOTTOM_LEFT_CORNER: &'static str = "╚";
-    pub const BOTTOM_RIGHT_CORNER: &'static str = "╝";
-    pub const VERTICAL_WALL: &'static str = "║";
-    pub const HORIZONTAL_WALL: &'static str = "═";
+    use termion::color::{self, Color};
+    use world::FoodKind;
+
+    /// The wall glyphs, indexed by which neighbors are walls too: 1 for the one above, 2 below,
+    /// 4 to the left and 8 to the right.
+    pub const WALLS: [&'static str; 16] = [
+        "■", "║", "║", "║",
+        "═", "╝", "╗", "╣",
+        "═", "╚", "╔", "╠",
+        "═", "╩", "╦", "╬",
+    ];
     pub const VERTICAL_SNAKE_BODY: &'static str = "║";
     pub const HORIZONTAL_SNAKE_BODY: &'static str = "═";
     pub const SNAKE_HEAD: &'static str = "@";
     pub const FOOD: &'static str = "o";
+    pub const BONUS_FOOD: &'static str = "$";
+    pub const SLOW_FOOD: &'static str = "~";
+    pub const SHRINK_FOOD: &'static str = "-";
+    pub const GHOST_FOOD: &'static str = "*";
     pub const GAME_OVER: &'static str = "╔═════════════════╗\n\r\
                                          ║───┬GAME OVER────║\n\r\
                                          ║ r ┆ replay      ║\n\r\
                                          ║ q ┆ quit        ║\n\r\
                                          ╚═══╧═════════════╝";
+    pub const YOU_WON: &'static str = "╔═════════════════╗\n\r\
+                                       ║───┬YOU WON!─────║\n\r\
+                                       ║ r ┆ replay      ║\n\r\
+                                       ║ q ┆ quit        ║\n\r\
+                                       ╚═══╧═════════════╝";
+    pub const ROUND_OVER: &'static str = "╔══════════════════════╗\n\r\
+                                          ║───────┬ROUND OVER────║\n\r\
+                                          ║ space ┆ next round   ║\n\r\
+                                          ║ q     ┆ quit         ║\n\r\
+                                          ╚═══════╧══════════════╝";
     pub const GAME_START_PROMPT: &'static str = "╔══════════════════════════════╗\n\r\
                                                  ║──Welcome to Snake for Redox──║\n\r\
                                                  ║──────────────────────────────║\n\r\
@@ -34,78 +68,108 @@ mod graphics {
                                                  ║ l ┆ right                    ║\n\r\
                                                  ╚═══╧══════════════════════════╝";
 
-}
-
-use self::graphics::*;
-
-#[derive(PartialEq, Copy, Clone)]
-enum Direction {
-    Up,
-    Down,
-    Left,
-    Right,
-}
-
-/// Snake's Body Part location and direction
-struct BodyPart {
-    x: u16,
-    y: u16,
-    direction: Direction,
-}
-
-/// Snake's Food
-struct Food {
-    x: u16,
-    y: u16,
-}
-
-impl BodyPart {
-    /// Move's the Snake along it's current direction
-    fn crawl(&self) -> BodyPart {
-        let mut x = self.x;
-        let mut y = self.y;
-
-        match self.direction {
-            Direction::Up => y += 1,
-            Direction::Down => y -= 1,
-            Direction::Left => x -= 1,
-            Direction::Right => x += 1,
-        }
 
-        BodyPart {
-            x: x,
-            y: y,
-            direction: self.direction,
+    /// Get the glyph and colour of a kind of food.
+    pub fn food(kind: FoodKind) -> (&'static str, &'static dyn Color) {
+        match kind {
+            FoodKind::Normal => (FOOD, &color::LightYellow),
+            FoodKind::Bonus => (BONUS_FOOD, &color::LightMagenta),
+            FoodKind::Slow => (SLOW_FOOD, &color::LightCyan),
+            FoodKind::Shrink => (SHRINK_FOOD, &color::LightRed),
+            FoodKind::Ghost => (GHOST_FOOD, &color::White),
         }
     }
 }
 
-/// Snake
-struct Snake {
-    direction: Direction,
-    body: VecDeque<BodyPart>,
-}
+use self::graphics::*;
+
+/// How long to wait between looks at the keyboard, in milliseconds, when there is nothing else to
+/// do.
+const POLL_INTERVAL: u64 = 10;
+/// The number of steps the game catches up on at most, after falling behind.
+const MAX_CATCH_UP: u32 = 3;
+/// The number of steps a simulated game lasts at most.
+const SIMULATION_STEPS: u32 = 10000;
+
+/// The help page.
+const HELP: &'static str = r#"
+snake ~ a simple snake implementation.
+
+rules:
+    Steer the snake to the food, which makes it grow longer and faster. The game is over
+    once the snake runs into a wall or into itself.
+
+flags:
+    -r | --height N ~ set the height of the play area (the terminal height by default, but
+                      for the status bar).
+    -c | --width N  ~ set the width of the play area (the terminal width by default).
+    -h | --help     ~ this help page.
+    --scores        ~ show the high scores. Only games played alone, without a bot, make it
+                      into the table.
+    --wrap          ~ leave the play area open, so the snake leaving one edge enters from the
+                      opposite one.
+    --map FILE      ~ play on the map drawn in FILE, where # is a wall and @ is the head of
+                      the snake, with its body trailing to the left. In versus mode, & is the
+                      head of the second snake, with its body trailing to the right.
+    --versus N      ~ two players on one keyboard, playing a match of N rounds. A round is
+                      lost by running into a wall, a snake or off the edge; running into
+                      each other head-on is a draw. Whoever wins most rounds wins the match.
+    --bot NAME      ~ let a bot steer the next snake, instead of a player. Given twice in
+                      versus mode, the second bot steers the second snake. The bots are:
+                      greedy   ~ heads straight for the food.
+                      bfs      ~ follows the shortest path to the food.
+                      hamilton ~ goes round and round a path through every cell, which
+                                 never runs into itself. Needs an even width or height.
+                      human is a player, for the first snake in a match against a bot.
+    --food SPEC     ~ set how likely every kind of food is to spawn, as weights of at most
+                      10000, in the form kind=weight,... The kinds left out keep their weight:
+                      normal=70 ~ o, a point. The snake grows and speeds up.
+                      bonus=10  ~ $, five points, but gone after 60 steps.
+                      slow=8    ~ ~, slows the game down for 100 steps.
+                      shrink=6  ~ -, takes 5 parts off the snake.
+                      ghost=6   ~ *, lets the snake pass through itself for 60 steps.
+    --simulate N    ~ play N games with every bot given (or every bot, if none is), without
+                      showing them, and print the average score of each. A game stops after
+                      10000 steps, if the snake is still alive by then.
+
+controls:
+    h | a | ← ~ turn left.
+    j | s | ↓ ~ turn down.
+    k | w | ↑ ~ turn up.
+    l | d | → ~ turn right.
+    p         ~ pause, or resume.
+    q         ~ quit.
+
+    Quick turns are remembered, and made one step after the other.
+
+    In versus mode, the first player (green) steers with wasd, and the second one (blue)
+    with hjkl or the arrow keys.
+"#;
 
 /// The game state.
 struct Game<R, W> {
-    /// The play area width.
-    width: usize,
-    /// The play area height.
-    height: usize,
-    /// Standard input.
-    stdin: R,
+    /// The game world.
+    world: World,
+    /// Standard input, as keys.
+    stdin: Keys<R>,
     /// Standard output.
     stdout: W,
-    /// Snake
-    snake: Snake,
-    /// Snake's Food
-    food: Food,
-    /// Speed
-    speed: u64,
-    /// Game Score
-    score: i32,
-    /// The randomizer
-    rand: Randomizer,
+    /// Is there room for the status bar below the play area?
+    status: bool,
+    /// The number of rounds of a versus match, or None when playing alone.
+    rounds: Option<u32>,
+    /// The rounds won by every player so far.
+    wins: Vec<u32>,
+    /// The rounds played so far.
+    played: u32,
+    /// The controller of every snake.
+    controllers: Vec<Box<dyn SnakeController>>,
+    /// Do the games go into the high-score table? Only those played alone, without a bot, do.
+    ranked: bool,
+    /// When the current game started, leaving out the time spent paused.
+    started: Instant,
+    /// When the game was paused, if it is.
+    paused: Option<Instant>,
 }
 
 impl<R: Read, W: Write> Game<R, W> {
@@ -116,261 +180,396 @@ impl<R: Read, W: Write> Game<R, W> {
         write!(self.stdout, "{}", cursor::Hide).unwrap();
         self.game_start_prompt();
         self.reset();
+
+        // The world is stepped at a fixed rate, however often the loop goes round. The lag is the
+        // time passed which has not been stepped through yet.
         let mut before = Instant::now();
+        let mut lag = Duration::from_millis(0);
 
         loop {
-            let interval = 1000 / self.speed;
             let now = Instant::now();
-            let dt = (now.duration_since(before).subsec_nanos() / 1_000_000) as u64;
-
-            if dt < interval {
-                sleep(Duration::from_millis(interval - dt));
-                continue;
-            }
-
+            lag += now.duration_since(before);
             before = now;
 
-            if !self.update() {
+            if !self.read_input() {
                 return;
             }
 
-            if self.check_game_over() {
-                if self.game_over() {
+            if self.paused.is_some() {
+                // Time stands still while the game is paused.
+                lag = Duration::from_millis(0);
+                sleep(Duration::from_millis(POLL_INTERVAL));
+                continue;
+            }
+
+            // Catch up, but not on a long hiccup, such as the terminal being suspended, and look
+            // at the keyboard again in between.
+            lag = lag.min(self.step_time() * MAX_CATCH_UP);
+            let mut steps = 0;
+            while lag >= self.step_time() && steps < MAX_CATCH_UP {
+                lag -= self.step_time();
+                steps += 1;
+
+                let events = self.step();
+                let over = events.iter().any(|event| match *event {
+                    Event::Died(_) | Event::Won(_) => true,
+                    _ => false,
+                });
+                if over {
+                    let again = match self.rounds {
+                        Some(rounds) => self.round_over(&events, rounds),
+                        None => self.game_over(&events),
+                    };
+
+                    if !again {
+                        return;
+                    }
+
                     self.reset();
-                    continue;
-                } else {
-                    return;
+                    before = Instant::now();
+                    lag = Duration::from_millis(0);
+                    break;
                 }
             }
 
-            if self.check_eating() {
-                self.score += 1;
-                self.speed += 4;
-                self.grow_snake();
-                self.move_food();
+            let step_time = self.step_time();
+            if lag < step_time {
+                sleep(step_time - lag);
             }
-
-            self.clear_snake();
-            self.draw_snake();
-            self.draw_food();
-
-            write!(self.stdout, "{}", style::Reset).unwrap();
-            self.stdout.flush().unwrap();
         }
     }
 
-    /// Reset the game.
+    /// Get the time a step takes, at the current speed.
     ///
-    /// This will display the starting play area.
-    fn reset(&mut self) {
-        write!(self.stdout, "{}{}", clear::All, style::Reset);
-
-        self.draw_walls();
-
-        self.snake = Snake {
-            direction: Direction::Right,
-            body: vec![
-                BodyPart { x: 10, y: 10, direction: Direction::Right},
-                BodyPart { x: 11, y: 10, direction: Direction::Right},
-                BodyPart { x: 12, y: 10, direction: Direction::Right},
-                BodyPart { x: 13, y: 10, direction: Direction::Right},
-                BodyPart { x: 14, y: 10, direction: Direction::Right},
-                BodyPart { x: 15, y: 10, direction: Direction::Right},
-                BodyPart { x: 16, y: 10, direction: Direction::Right},
-                BodyPart { x: 17, y: 10, direction: Direction::Right},
-                BodyPart { x: 18, y: 10, direction: Direction::Right},
-                BodyPart { x: 19, y: 10, direction: Direction::Right},
-            ].into_iter().collect(),
-        };
-
-        self.food = Food {
-            x: self.width as u16 / 2,
-            y: self.height as u16 / 2,
-        };
-
-        self.score = 0;
-        self.speed = 10;
+    /// This is never zero, so that the game loop always gets on.
+    fn step_time(&self) -> Duration {
+        Duration::from_nanos((1_000_000_000 / self.world.rate().max(1)).max(1))
     }
 
-    /// Update the game.
+    /// Step the world once, taking a turn from every controller, and draw it.
     ///
-    /// This will receive and process input. As well as update the game world.
-    /// Returns false if the game is supposed to be closed.
-    fn update(&mut self) -> bool {
-        let mut key_bytes = [0];
-        self.stdin.read(&mut key_bytes).unwrap();
-
-        self.rand.write_u8(key_bytes[0]);
-
-        match key_bytes[0] {
-            b'q' => return false,
-            b'k' | b'w' => self.turn_snake(Direction::Up),
-            b'j' | b's' => self.turn_snake(Direction::Down),
-            b'h' | b'a' => self.turn_snake(Direction::Left),
-            b'l' | b'd' => self.turn_snake(Direction::Right),
-            _ => {},
+    /// Returns what happened.
+    fn step(&mut self) -> Vec<Event> {
+        let world = &self.world;
+        let turns: Vec<Option<Direction>> = self.controllers.iter_mut().enumerate()
+            .map(|(i, controller)| controller.control(world, i))
+            .collect();
+
+        self.clear_snakes();
+        self.clear_food();
+        let events = self.world.step(&turns);
+
+        for i in 0..self.world.snakes.len() {
+            self.draw_snake(i);
         }
+        self.draw_food();
+        self.draw_status();
 
-        self.move_snake();
+        write!(self.stdout, "{}", style::Reset).unwrap();
+        self.stdout.flush().unwrap();
 
-        true
+        events
     }
 
-    /// Check if the Snake is overlapping a wall or a body part
-    fn check_game_over(&mut self) -> bool {
-        let head = &self.snake.body.back().unwrap();
+    /// Pause the game, or resume it.
+    ///
+    /// The time spent paused does not count towards the duration of the game.
+    fn toggle_pause(&mut self) {
+        match self.paused.take() {
+            Some(paused) => self.started += paused.elapsed(),
+            None => self.paused = Some(Instant::now()),
+        }
 
-        self.snake.body.iter().filter(|part| (head.x, head.y) == (part.x, part.y)).count() > 1
-        || head.x == 0
-        || head.y == 0
-        || head.x == self.width as u16
-        || head.y == self.height as u16 - 1
+        self.draw_status();
+        self.stdout.flush().unwrap();
     }
 
-    /// Grows the Snake's tail
-    fn grow_snake(&mut self) {
-        let x;
-        let y;
-        let direction;
+    /// Reset the game.
+    ///
+    /// This will display the starting play area.
+    fn reset(&mut self) {
+        write!(self.stdout, "{}{}", clear::All, style::Reset);
 
-        {
-            let tail = &self.snake.body.front().unwrap();
+        self.draw_walls();
 
-            x = match tail.direction {
-                Direction::Left => tail.x + 1,
-                Direction::Right => tail.x - 1,
-                _ => tail.x,
-            };
+        self.world.reset();
+        self.started = Instant::now();
+        self.paused = None;
+    }
+
+    /// Read the keys pressed since the last look at the keyboard.
+    ///
+    /// This passes the keys steering the snakes on to their controllers, which queue them up.
+    /// The first player steers with wasd and the second one with hjkl or the arrow keys; when
+    /// playing alone, any of them will do. Returns false if the game is supposed to be closed.
+    fn read_input(&mut self) -> bool {
+        while let Some(Ok(key)) = self.stdin.next() {
+            if let Key::Char(c) = key {
+                self.world.rand.write_u8(c as u8);
+            }
 
-            y = match tail.direction {
-                Direction::Up => tail.y + 1,
-                Direction::Down => tail.y - 1,
-                _ => tail.y,
+            let (player, direction) = match key {
+                Key::Char('q') => return false,
+                Key::Char('p') => {
+                    self.toggle_pause();
+                    continue;
+                },
+                Key::Char('w') => (0, Direction::Up),
+                Key::Char('s') => (0, Direction::Down),
+                Key::Char('a') => (0, Direction::Left),
+                Key::Char('d') => (0, Direction::Right),
+                Key::Char('k') | Key::Up => (1, Direction::Up),
+                Key::Char('j') | Key::Down => (1, Direction::Down),
+                Key::Char('h') | Key::Left => (1, Direction::Left),
+                Key::Char('l') | Key::Right => (1, Direction::Right),
+                _ => continue,
             };
 
-            direction = tail.direction;
+            // No turning while the game is paused.
+            if self.paused.is_none() {
+                let player = player.min(self.controllers.len() - 1);
+                self.controllers[player].key(direction);
+            }
         }
 
-        self.snake.body.push_front(BodyPart {
-            x: x,
-            y: y,
-            direction: direction,
-        });
+        true
     }
 
-    /// Checks if the Snake is overlapping the food
-    fn check_eating(&mut self) -> bool {
-        let head = &self.snake.body.back().unwrap();
-        (head.x, head.y) == (self.food.x, self.food.y)
+    /// Wait for a key to be pressed.
+    fn wait_key(&mut self) -> Key {
+        loop {
+            match self.stdin.next() {
+                Some(Ok(key)) => {
+                    if let Key::Char(c) = key {
+                        self.world.rand.write_u8(c as u8);
+                    }
+
+                    return key;
+                },
+                _ => sleep(Duration::from_millis(POLL_INTERVAL)),
+            }
+        }
     }
 
-    fn clear_snake(&mut self) {
-        for part in &self.snake.body {
+    /// Clears every snake off the screen.
+    fn clear_snakes(&mut self) {
+        for part in self.world.snakes.iter().flat_map(|snake| snake.body.iter()) {
             write!(self.stdout, "{} ", cursor::Goto(part.x + 1, part.y + 1)).unwrap();
         }
     }
 
-    fn move_snake(&mut self) {
-        {
-            let tail = self.snake.body.pop_front().unwrap();
-            write!(self.stdout, "{} ", cursor::Goto(tail.x + 1, tail.y + 1)).unwrap();
-        }
-
-        for part in self.snake.body.iter_mut() {
-            part.crawl();
-        }
+    fn game_start_prompt(&mut self) {
+        write!(self.stdout, "{}{}", cursor::Goto(1, 1), GAME_START_PROMPT).unwrap();
+        self.stdout.flush().unwrap();
+        while self.wait_key() != Key::Char(' ') {}
+    }
 
-        let (x, y, direction) = {
-            let head = self.snake.body.back().unwrap();
+    /// The game is over, with the given events of the last step.
+    ///
+    /// This shows the score, lets the player enter their initials if it is a high score, and
+    /// shows the high-score table. Returns false if the game is supposed to be closed.
+    fn game_over(&mut self, events: &[Event]) -> bool {
+        // Clear the rest of every line written over the play area.
+        let eol = format!("{}\n\r", clear::UntilNewline);
+        let score = Score {
+            score: self.world.snakes[0].score,
+            length: self.world.snakes[0].body.len(),
+            duration: millis(self.started.elapsed()),
+            date: SystemTime::now().duration_since(UNIX_EPOCH).map(|date| date.as_secs()).unwrap_or(0),
+            name: String::new(),
+        };
 
-            match self.snake.direction {
-                Direction::Up => (head.x, head.y - 1, Direction::Up),
-                Direction::Down => (head.x, head.y + 1, Direction::Down),
-                Direction::Left => (head.x - 1, head.y, Direction::Left),
-                Direction::Right => (head.x + 1, head.y, Direction::Right),
+        // The snake may have filled the whole map instead of dying.
+        let title = if events.contains(&Event::Won(0)) { YOU_WON } else { GAME_OVER };
+        write!(self.stdout, "{}{}{}", cursor::Goto(1, 1), title, eol).unwrap();
+        write!(self.stdout, "{}SCORE: {}, LENGTH: {}, TIME: {}{}", eol, score.score, score.length,
+               scores::format_duration(score.duration), eol).unwrap();
+
+        if self.ranked {
+            let mut scores = Scores::load();
+
+            if scores.qualifies(score.score) {
+                write!(self.stdout, "{}New high score! Initials: {}", eol, clear::UntilNewline).unwrap();
+                if let Some(name) = self.read_initials() {
+                    scores.insert(Score {
+                        name: name,
+                        ..score
+                    });
+
+                    if let Err(err) = scores.save() {
+                        write!(self.stdout, "{}could not save high scores: {}", eol, err).unwrap();
+                    }
+                }
+                write!(self.stdout, "{}", eol).unwrap();
             }
-        };
 
-        self.snake.body.push_back(BodyPart {
-            x: x,
-            y: y,
-            direction: direction
-        });
-    }
+            write!(self.stdout, "{}", eol).unwrap();
+            scores.write_table(&mut self.stdout, &eol).unwrap();
+        }
+        self.stdout.flush().unwrap();
 
-    fn turn_snake(&mut self, direction: Direction) {
-        match (direction, self.snake.direction) {
-            (Direction::Up, Direction::Down)
-            | (Direction::Down, Direction::Up)
-            | (Direction::Left, Direction::Right)
-            | (Direction::Right, Direction::Left) => return,
-            _ => self.snake.direction = direction,
+        loop {
+            match self.wait_key() {
+                Key::Char('r') => return true,
+                Key::Char('q') => return false,
+                _ => {},
+            }
         }
     }
 
-    fn game_start_prompt(&mut self) {
-        write!(self.stdout, "{}{}", cursor::Goto(1, 1), GAME_START_PROMPT).unwrap();
+    /// Read up to three initials, echoing them.
+    ///
+    /// Returns `None` if the player pressed escape instead.
+    fn read_initials(&mut self) -> Option<String> {
+        let mut initials = String::new();
         self.stdout.flush().unwrap();
+
         loop {
-            let mut buf = [0];
-            self.stdin.read(&mut buf).unwrap();
-            self.rand.write_u8(buf[0]);
-            if buf[0] == b' ' {
-                return;
+            match self.wait_key() {
+                Key::Char('\r') | Key::Char('\n') if !initials.is_empty() => return Some(initials),
+                Key::Char(c) if c.is_ascii_alphanumeric() && initials.len() < 3 => {
+                    let c = c.to_ascii_uppercase();
+                    initials.push(c);
+                    write!(self.stdout, "{}", c).unwrap();
+                },
+                Key::Backspace => if initials.pop().is_some() {
+                    write!(self.stdout, "{} {}", cursor::Left(1), cursor::Left(1)).unwrap();
+                },
+                Key::Esc => return None,
+                _ => {},
             }
+
+            self.stdout.flush().unwrap();
         }
     }
 
-    fn game_over(&mut self) -> bool {
-        write!(self.stdout, "{}{}", cursor::Goto(1, 1), GAME_OVER).unwrap();
-        write!(self.stdout, "{}", cursor::Goto((self.width as u16 / 2) - 2, self.height as u16 / 2 + 1)).unwrap();
-        write!(self.stdout, "SCORE: {}", self.score).unwrap();
+    /// Ends a round of a versus match, showing who won it, and who won the match once it is over.
+    ///
+    /// Returns false if the game is supposed to be closed.
+    fn round_over(&mut self, events: &[Event], rounds: u32) -> bool {
+        let dead: Vec<usize> = events.iter().filter_map(|event| match *event {
+            Event::Died(i) => Some(i),
+            _ => None,
+        }).collect();
+
+        // A snake filling the map wins the round, and otherwise the survivor does, so snakes
+        // dying together make a draw.
+        let filled = events.iter().filter_map(|event| match *event {
+            Event::Won(i) => Some(i),
+            _ => None,
+        }).next();
+        let winner = filled.or_else(|| (0..self.world.snakes.len()).find(|i| !dead.contains(i)));
+        if let Some(winner) = winner {
+            self.wins[winner] += 1;
+        }
+        self.played += 1;
+
+        let over = self.played >= rounds || self.wins.iter().any(|&wins| wins > rounds / 2);
+        let result = if over {
+            match self.wins[0].cmp(&self.wins[1]) {
+                Ordering::Greater => "PLAYER 1 WINS THE MATCH".to_owned(),
+                Ordering::Less => "PLAYER 2 WINS THE MATCH".to_owned(),
+                Ordering::Equal => "THE MATCH IS A DRAW".to_owned(),
+            }
+        } else {
+            match winner {
+                Some(winner) => format!("PLAYER {} WINS ROUND {}", winner + 1, self.played),
+                None => format!("ROUND {} IS A DRAW", self.played),
+            }
+        };
+
+        write!(self.stdout, "{}{}", cursor::Goto(1, 1), if over { GAME_OVER } else { ROUND_OVER }).unwrap();
+
+        let x = (self.world.map.width / 2).saturating_sub(10).max(1);
+        let y = self.world.map.height / 2 + 1;
+        write!(self.stdout, "{}{}", cursor::Goto(x, y), result).unwrap();
+        for (i, snake) in self.world.snakes.iter().enumerate() {
+            write!(self.stdout, "{}PLAYER {}: {} food, {} of {} rounds won", cursor::Goto(x, y + 1 + i as u16),
+                   i + 1, snake.score, self.wins[i], rounds).unwrap();
+        }
         self.stdout.flush().unwrap();
 
         loop {
-            // Repeatedly read a single byte.
-            let mut buf = [0];
-            self.stdin.read(&mut buf).unwrap();
-
-            match buf[0] {
-                b'r' => return true,
-                b'q' => return false,
+            match self.wait_key() {
+                Key::Char('r') if over => {
+                    // Start a new match.
+                    self.wins = vec![0; self.wins.len()];
+                    self.played = 0;
+                    return true;
+                },
+                Key::Char(' ') if !over => return true,
+                Key::Char('q') => return false,
                 _ => {},
             }
         }
     }
 
-    fn draw_horizontal_line(&mut self, chr: &str, width: u16) {
-        for _ in 0..width { self.stdout.write(chr.as_bytes()).unwrap(); }
+    /// Clears the snake's food off the screen.
+    fn clear_food(&mut self) {
+        write!(self.stdout, "{} ", cursor::Goto(self.world.food.x + 1, self.world.food.y + 1)).unwrap();
     }
 
-    /// Move the snake's food.
-    fn move_food(&mut self) {
-        loop {
-            let x = (self.rand.read_u8() as u16 % (self.width as u16 - 2)) + 1;
-            let y = (self.rand.read_u8() as u16 % (self.height as u16 - 2)) + 1;
+    /// Draws the snake's food, in the glyph and colour of its kind.
+    fn draw_food(&mut self) {
+        let (glyph, colour) = food(self.world.food.kind);
+        write!(self.stdout, "{}{}{}{}", cursor::Goto(self.world.food.x + 1, self.world.food.y + 1),
+               color::Fg(colour), glyph, color::Fg(color::Reset)).unwrap();
+    }
 
-            if self.snake.body.iter().filter(|part| {
-                (x, y) == (part.x, part.y)
-            }).next().is_some() {
-                continue;
+    /// Draws the status bar below the play area, with the scores and the effects still going on,
+    /// along with the steps they have left.
+    fn draw_status(&mut self) {
+        if !self.status {
+            return;
+        }
+
+        write!(self.stdout, "{}{}", cursor::Goto(1, self.world.map.height + 1), clear::CurrentLine).unwrap();
+
+        let versus = self.world.snakes.len() > 1;
+        for (i, snake) in self.world.snakes.iter().enumerate() {
+            if versus {
+                write!(self.stdout, "P{}: {}  ", i + 1, snake.score).unwrap();
             } else {
-                self.food.x = x;
-                self.food.y = y;
-                break;
+                write!(self.stdout, "SCORE: {}  ", snake.score).unwrap();
             }
-        };
-    }
+        }
 
-    /// Draws the snake's food.
-    fn draw_food(&mut self) {
-        write!(self.stdout, "{}", cursor::Goto(self.food.x + 1, self.food.y + 1)).unwrap();
-        self.stdout.write(FOOD.as_bytes()).unwrap();
+        let mut effects = Vec::new();
+        for (i, snake) in self.world.snakes.iter().enumerate() {
+            if snake.ghost > 0 {
+                let name = if versus { format!("P{} GHOST", i + 1) } else { "GHOST".to_owned() };
+                effects.push((FoodKind::Ghost, name, snake.ghost));
+            }
+        }
+        if self.world.slow > 0 {
+            effects.push((FoodKind::Slow, "SLOW".to_owned(), self.world.slow));
+        }
+        if let Some(expires) = self.world.food.expires {
+            effects.push((self.world.food.kind, "BONUS".to_owned(), expires));
+        }
+
+        if self.paused.is_some() {
+            write!(self.stdout, "PAUSED  ").unwrap();
+        }
+
+        for (kind, name, steps) in effects {
+            let (glyph, colour) = food(kind);
+            write!(self.stdout, "{}{} {} {}{}  ", color::Fg(colour), glyph, name, steps,
+                   color::Fg(color::Reset)).unwrap();
+        }
     }
 
-    /// Draws the snake.
-    fn draw_snake(&mut self) {
-        for part in &self.snake.body {
+    /// Draws a snake, in the colour of its player in versus mode.
+    fn draw_snake(&mut self, snake: usize) {
+        if self.rounds.is_some() {
+            match snake {
+                0 => write!(self.stdout, "{}", color::Fg(color::LightGreen)).unwrap(),
+                _ => write!(self.stdout, "{}", color::Fg(color::LightBlue)).unwrap(),
+            }
+        }
+
+        let snake = &self.world.snakes[snake];
+        for part in &snake.body {
             write!(self.stdout, "{}", cursor::Goto(part.x + 1, part.y + 1)).unwrap();
             match part.direction {
                 Direction::Up | Direction::Down => self.stdout.write(VERTICAL_SNAKE_BODY.as_bytes()).unwrap(),
@@ -378,63 +577,67 @@ impl<R: Read, W: Write> Game<R, W> {
             };
         }
 
-        let head = self.snake.body.back().unwrap();
+        let head = snake.head();
 
         write!(self.stdout, "{}", cursor::Goto(head.x + 1, head.y + 1)).unwrap();
         self.stdout.write(SNAKE_HEAD.as_bytes()).unwrap();
+        write!(self.stdout, "{}", color::Fg(color::Reset)).unwrap();
     }
 
     /// Draws the game walls.
+    ///
+    /// Every wall is drawn joined up with its neighbors, so any layout looks right.
     fn draw_walls(&mut self) {
-        let width: u16 = self.width as u16;
-        let height: u16 = self.height as u16;
+        let map = &self.world.map;
 
         write!(self.stdout, "{}", color::Fg(color::Red)).unwrap();
 
-        write!(self.stdout, "{}{}", cursor::Goto(1, 1), TOP_LEFT_CORNER).unwrap();
-        write!(self.stdout, "{}", cursor::Goto(2, 1)).unwrap();
-        self.draw_horizontal_line(HORIZONTAL_WALL, width - 2);
-        write!(self.stdout, "{}{}", cursor::Goto(width, 1), TOP_RIGHT_CORNER).unwrap();
+        for y in 0..map.height {
+            for x in 0..map.width {
+                if !map.is_wall(x, y) {
+                    continue;
+                }
+
+                let neighbors = (y > 0 && map.is_wall(x, y - 1)) as usize
+                    | (y + 1 < map.height && map.is_wall(x, y + 1)) as usize * 2
+                    | (x > 0 && map.is_wall(x - 1, y)) as usize * 4
+                    | (x + 1 < map.width && map.is_wall(x + 1, y)) as usize * 8;
 
-        for y in 1..height {
-            write!(self.stdout, "{}{}", cursor::Goto(1, y as u16 + 1), VERTICAL_WALL).unwrap();
-            write!(self.stdout, "{}{}", cursor::Goto(self.width as u16, y as u16 + 1), VERTICAL_WALL).unwrap();
+                write!(self.stdout, "{}{}", cursor::Goto(x + 1, y + 1), WALLS[neighbors]).unwrap();
+            }
         }
 
-        write!(self.stdout, "{}{}", cursor::Goto(1, height), BOTTOM_LEFT_CORNER).unwrap();
-        write!(self.stdout, "{}", cursor::Goto(2, height)).unwrap();
-        self.draw_horizontal_line(HORIZONTAL_WALL, width - 2);
-        write!(self.stdout, "{}{}", cursor::Goto(width, height), BOTTOM_RIGHT_CORNER).unwrap();
-
         write!(self.stdout, "{}", color::Fg(color::Reset)).unwrap();
     }
 }
 
 /// Initializes the game.
-fn init(width: usize, height: usize) {
+fn init(map: Map, wrap: bool, weights: [u32; 5], rounds: Option<u32>, controllers: Vec<Box<dyn SnakeController>>,
+        ranked: bool) {
     let stdout = stdout();
     let mut stdout = stdout.lock().into_raw_mode().unwrap();
-    let stdin = async_stdin();
+    let stdin = async_stdin().keys();
 
     write!(stdout, "{}{}", clear::All, cursor::Goto(1, 1)).unwrap();
     stdout.flush().unwrap();
 
+    // The status bar goes on the line below the play area, if the terminal has one.
+    let status = termion::terminal_size().map_or(true, |(_, height)| height > map.height);
+    let mut world = World::new(map, wrap, controllers.len(), Randomizer::new(0));
+    world.weights = weights;
+
     let mut game = Game {
-        width: width,
-        height: height,
+        world: world,
         stdin: stdin,
         stdout: stdout,
-        snake: Snake {
-            direction: Direction::Right,
-            body: VecDeque::new(),
-        },
-        food: Food {
-            x: 0,
-            y: 0,
-        },
-        score: 0,
-        speed: 0,
-        rand: Randomizer::new(0),
+        status: status,
+        rounds: rounds,
+        wins: vec![0; 2],
+        played: 0,
+        controllers: controllers,
+        ranked: ranked,
+        started: Instant::now(),
+        paused: None,
     };
 
     game.reset();
@@ -444,6 +647,267 @@ fn init(width: usize, height: usize) {
     game.stdout.flush().unwrap();
 }
 
+/// Convert a duration to milliseconds.
+fn millis(duration: Duration) -> u64 {
+    duration.as_secs() * 1000 + (duration.subsec_nanos() / 1_000_000) as u64
+}
+
+/// Play games with bots, without showing them, and print the average score of each bot.
+fn simulate<W: Write>(stdout: &mut W, map: &Map, wrap: bool, weights: [u32; 5], games: u32, bots: &[String]) {
+    for name in bots {
+        let mut score = 0;
+        let mut deaths = 0;
+
+        for game in 0..games {
+            let mut bot = controller::from_name(name, map).unwrap();
+            let mut world = World::new(map.clone(), wrap, 1, Randomizer::new(game as u64));
+            world.weights = weights;
+
+            for _ in 0..SIMULATION_STEPS {
+                let turn = bot.control(&world, 0);
+                let events = world.step(&[turn]);
+                if events.contains(&Event::Died(0)) {
+                    deaths += 1;
+                    break;
+                }
+                if events.contains(&Event::Won(0)) {
+                    break;
+                }
+            }
+
+            score += world.snakes[0].score;
+        }
+
+        writeln!(stdout, "{:8} ~ average score {:.1} over {} games, {} of which ended in death.", name,
+                 score as f64 / games as f64, games, deaths).unwrap();
+    }
+
+    stdout.flush().unwrap();
+}
+
 fn main() {
-    init(80, 40);
+    let mut args = env::args().skip(1);
+    let mut width = None;
+    let mut height = None;
+    let mut wrap = false;
+    let mut map = None;
+    let mut rounds = None;
+    let mut bots = Vec::new();
+    let mut games = None;
+    let mut weights = world::DEFAULT_WEIGHTS;
+
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    let stderr = io::stderr();
+    let mut stderr = stderr.lock();
+
+    loop {
+        // Read the arguments.
+        // Does not use a for loop because each argument may have second parameter.
+
+        let arg = if let Some(x) = args.next() {
+            x
+        } else {
+            break;
+        };
+
+        match arg.as_str() {
+            "-r" | "--height" => if height.is_none() {
+                height = Some(args.next().unwrap_or_else(|| {
+                    stderr.write(b"no height given.\n").unwrap();
+                    stderr.flush().unwrap();
+                    process::exit(1);
+                }).parse().unwrap_or_else(|_| {
+                    stderr.write(b"invalid integer given.\n").unwrap();
+                    stderr.flush().unwrap();
+                    process::exit(1);
+                }));
+            } else {
+                stderr.write(b"you may only input one height.\n").unwrap();
+                stderr.flush().unwrap();
+                process::exit(1);
+            },
+            "-c" | "--width" => if width.is_none() {
+                width = Some(args.next().unwrap_or_else(|| {
+                    stderr.write(b"no width given.\n").unwrap();
+                    stderr.flush().unwrap();
+                    process::exit(1);
+                }).parse().unwrap_or_else(|_| {
+                    stderr.write(b"invalid integer given.\n").unwrap();
+                    stderr.flush().unwrap();
+                    process::exit(1);
+                }));
+            } else {
+                stderr.write(b"you may only input one width.\n").unwrap();
+                stderr.flush().unwrap();
+                process::exit(1);
+            },
+            "-h" | "--help" => {
+                // Print the help page.
+                stdout.write(HELP.as_bytes()).unwrap();
+                stdout.flush().unwrap();
+                process::exit(0);
+            },
+            "--scores" => {
+                // Print the high scores.
+                Scores::load().write_table(&mut stdout, "\n").unwrap();
+                stdout.flush().unwrap();
+                process::exit(0);
+            },
+            "--wrap" => wrap = true,
+            "--food" => {
+                weights = args.next().and_then(|spec| world::parse_weights(&spec)).unwrap_or_else(|| {
+                    stderr.write(b"no valid food weights given (e.g. normal=70,bonus=10, at most 10000 each).\n").unwrap();
+                    stderr.flush().unwrap();
+                    process::exit(1);
+                });
+            },
+            "--map" => if map.is_none() {
+                map = Some(args.next().unwrap_or_else(|| {
+                    stderr.write(b"no map file given.\n").unwrap();
+                    stderr.flush().unwrap();
+                    process::exit(1);
+                }));
+            } else {
+                stderr.write(b"you may only input one map.\n").unwrap();
+                stderr.flush().unwrap();
+                process::exit(1);
+            },
+            "--versus" => if rounds.is_none() {
+                let n = args.next().unwrap_or_else(|| {
+                    stderr.write(b"no number of rounds given.\n").unwrap();
+                    stderr.flush().unwrap();
+                    process::exit(1);
+                }).parse().unwrap_or_else(|_| {
+                    stderr.write(b"invalid integer given.\n").unwrap();
+                    stderr.flush().unwrap();
+                    process::exit(1);
+                });
+                if n == 0 {
+                    stderr.write(b"a match has at least one round.\n").unwrap();
+                    stderr.flush().unwrap();
+                    process::exit(1);
+                }
+                rounds = Some(n);
+            } else {
+                stderr.write(b"you may only input one number of rounds.\n").unwrap();
+                stderr.flush().unwrap();
+                process::exit(1);
+            },
+            "--bot" => {
+                let name = args.next().unwrap_or_else(|| {
+                    stderr.write(b"no bot given.\n").unwrap();
+                    stderr.flush().unwrap();
+                    process::exit(1);
+                });
+                if !controller::NAMES.contains(&name.as_str()) {
+                    stderr.write(b"no valid bot given (greedy, bfs, hamilton or human).\n").unwrap();
+                    stderr.flush().unwrap();
+                    process::exit(1);
+                }
+                bots.push(name);
+            },
+            "--simulate" => if games.is_none() {
+                games = Some(args.next().unwrap_or_else(|| {
+                    stderr.write(b"no number of games given.\n").unwrap();
+                    stderr.flush().unwrap();
+                    process::exit(1);
+                }).parse().unwrap_or_else(|_| {
+                    stderr.write(b"invalid integer given.\n").unwrap();
+                    stderr.flush().unwrap();
+                    process::exit(1);
+                }));
+            } else {
+                stderr.write(b"you may only input one number of games.\n").unwrap();
+                stderr.flush().unwrap();
+                process::exit(1);
+            },
+            _ => {
+                stderr.write(b"Unknown argument.\n").unwrap();
+                stderr.flush().unwrap();
+                process::exit(1);
+            }
+        }
+    }
+
+    let map = match map {
+        // A map file brings its own size.
+        Some(path) => {
+            let map = File::open(&path).map_err(map::Error::Io).and_then(|file| {
+                Map::read(BufReader::new(file))
+            }).unwrap_or_else(|err| {
+                writeln!(stderr, "{}: {}", path, err).unwrap();
+                stderr.flush().unwrap();
+                process::exit(1);
+            });
+
+            if width.map_or(false, |w| w != map.width) || height.map_or(false, |h| h != map.height) {
+                writeln!(stderr, "{}: the map does not match the given size.", path).unwrap();
+                stderr.flush().unwrap();
+                process::exit(1);
+            }
+            if rounds.is_some() && map.start2.is_none() {
+                writeln!(stderr, "{}: the map has no start (&) for the second snake.", path).unwrap();
+                stderr.flush().unwrap();
+                process::exit(1);
+            }
+
+            map
+        },
+        // Otherwise, the play area fills the terminal, and is walled off unless it wraps around.
+        None => {
+            // The last line of the terminal is left for the status bar.
+            let (w, h) = termion::terminal_size().unwrap_or((80, 40));
+            let (width, height) = (width.unwrap_or(w), height.unwrap_or(h.saturating_sub(1)));
+
+            if wrap {
+                Map::open(width, height)
+            } else {
+                Map::bordered(width, height)
+            }.unwrap_or_else(|| {
+                writeln!(stderr, "the play area must be at least {}x5.", map::START_LENGTH + 4).unwrap();
+                stderr.flush().unwrap();
+                process::exit(1);
+            })
+        },
+    };
+
+    // Every bot needs to be able to play on the map.
+    for name in bots.iter() {
+        if let Err(err) = controller::from_name(name, &map) {
+            writeln!(stderr, "{}: {}.", name, err).unwrap();
+            stderr.flush().unwrap();
+            process::exit(1);
+        }
+    }
+
+    if let Some(games) = games {
+        if games == 0 || bots.iter().any(|name| name == "human") {
+            stderr.write(b"only bots can be simulated, in one game or more.\n").unwrap();
+            stderr.flush().unwrap();
+            process::exit(1);
+        }
+        if bots.is_empty() {
+            bots = controller::NAMES.iter().filter(|&&name| name != "human").map(|&name| name.to_owned()).collect();
+            bots.retain(|name| controller::from_name(name, &map).is_ok());
+        }
+
+        simulate(&mut stdout, &map, wrap, weights, games, &bots);
+        return;
+    }
+
+    let players = if rounds.is_some() { 2 } else { 1 };
+    if bots.len() > players {
+        stderr.write(b"there are more bots than snakes to steer.\n").unwrap();
+        stderr.flush().unwrap();
+        process::exit(1);
+    }
+
+    // The bots steer the first snakes, and players the rest.
+    let controllers = (0..players).map(|i| {
+        controller::from_name(bots.get(i).map_or("human", |name| name.as_str()), &map).unwrap()
+    }).collect();
+
+    drop(stdout);
+    init(map, wrap, weights, rounds, controllers, rounds.is_none() && bots.iter().all(|name| name == "human"));
 }
@@ -0,0 +1,173 @@
+//! Level maps.
+//!
+//! A map is the layout of the walls in the play area, along with where the snake starts. Maps can
+//! be drawn in text files, one character per cell:
+//!
+//! ```text
+//! ####################
+//! #                  #
+//! #   @      ####    #
+//! #          #       #
+//! #                  #
+//! ####################
+//! ```
+//!
+//! `#` is a wall and `@` is the head of the snake, whose body trails to the left of it. In versus
+//! mode, `&` is the head of the second snake, whose body trails to the right of it. Anything else
+//! is free space. Short lines are padded with free space.
+
+use std::fmt;
+use std::io::{self, BufRead};
+
+/// The length of the snake at the start of a game.
+pub const START_LENGTH: u16 = 10;
+
+/// Error reading a map file.
+#[derive(Debug)]
+pub enum Error {
+    /// The file could not be read.
+    Io(io::Error),
+    /// The map is not playable.
+    Invalid(&'static str),
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        Error::Io(err)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Io(ref err) => write!(f, "{}", err),
+            Error::Invalid(msg) => write!(f, "invalid map: {}", msg),
+        }
+    }
+}
+
+/// The layout of a play area.
+#[derive(Clone)]
+pub struct Map {
+    /// The width of the play area.
+    pub width: u16,
+    /// The height of the play area.
+    pub height: u16,
+    /// The walls, row by row.
+    walls: Vec<bool>,
+    /// Where the head of the snake starts.
+    pub start: (u16, u16),
+    /// Where the head of the second snake starts, if there is room for one.
+    pub start2: Option<(u16, u16)>,
+}
+
+impl Map {
+    /// Create a map with no walls at all.
+    ///
+    /// Returns None if the snake does not fit.
+    pub fn open(width: u16, height: u16) -> Option<Map> {
+        Map::new(width, height, false)
+    }
+
+    /// Create a map walled off along its border.
+    ///
+    /// Returns None if the snake does not fit.
+    pub fn bordered(width: u16, height: u16) -> Option<Map> {
+        Map::new(width, height, true)
+    }
+
+    /// Create an empty map, with or without a border, and the snake in the upper left quarter.
+    ///
+    /// The second snake starts opposite the first one, in the lower right quarter.
+    fn new(width: u16, height: u16, border: bool) -> Option<Map> {
+        if width < START_LENGTH + 4 || height < 5 {
+            return None;
+        }
+
+        let mut walls = vec![false; width as usize * height as usize];
+        if border {
+            for y in 0..height {
+                for x in 0..width {
+                    walls[y as usize * width as usize + x as usize] =
+                        x == 0 || y == 0 || x == width - 1 || y == height - 1;
+                }
+            }
+        }
+
+        let start = ((START_LENGTH + 9).min(width - 3), 10.min(height / 4).max(1));
+        Some(Map {
+            width: width,
+            height: height,
+            walls: walls,
+            start: start,
+            start2: Some((width - 1 - start.0, height - 1 - start.1)),
+        })
+    }
+
+    /// Read a map file.
+    pub fn read<R: BufRead>(from: R) -> Result<Map, Error> {
+        let mut rows = Vec::new();
+        for line in from.lines() {
+            rows.push(line?.trim_end().chars().collect::<Vec<char>>());
+        }
+
+        // Trailing blank lines are not part of the map.
+        while rows.last().map_or(false, |row| row.is_empty()) {
+            rows.pop();
+        }
+
+        let width = rows.iter().map(|row| row.len()).max().unwrap_or(0);
+        let height = rows.len();
+        if width == 0 {
+            return Err(Error::Invalid("the map is empty"));
+        }
+        if width > 0xFFFF || height > 0xFFFF {
+            return Err(Error::Invalid("the map is too large"));
+        }
+
+        let mut walls = vec![false; width * height];
+        let mut start = None;
+        let mut start2 = None;
+        for (y, row) in rows.iter().enumerate() {
+            for (x, &c) in row.iter().enumerate() {
+                match c {
+                    '#' => walls[y * width + x] = true,
+                    '@' if start.is_some() => return Err(Error::Invalid("the snake starts twice")),
+                    '@' => start = Some((x as u16, y as u16)),
+                    '&' if start2.is_some() => return Err(Error::Invalid("the second snake starts twice")),
+                    '&' => start2 = Some((x as u16, y as u16)),
+                    _ => {},
+                }
+            }
+        }
+
+        let map = Map {
+            width: width as u16,
+            height: height as u16,
+            walls: walls,
+            start: start.ok_or(Error::Invalid("the snake has no start"))?,
+            start2: start2,
+        };
+
+        let (x, y) = map.start;
+        if x + 1 < START_LENGTH || (x + 1 - START_LENGTH..x + 1).any(|x| map.is_wall(x, y)) {
+            return Err(Error::Invalid("no room for the snake left of its start"));
+        }
+
+        if let Some((x, y)) = map.start2 {
+            if (x..x + START_LENGTH).any(|x| map.is_wall(x, y)) {
+                return Err(Error::Invalid("no room for the second snake right of its start"));
+            }
+        }
+
+        Ok(map)
+    }
+
+    /// Is (x, y) a wall?
+    ///
+    /// Everything outside the map counts as a wall.
+    pub fn is_wall(&self, x: u16, y: u16) -> bool {
+        x >= self.width || y >= self.height
+            || self.walls[y as usize * self.width as usize + x as usize]
+    }
+}
@@ -0,0 +1,179 @@
+//! The high-score table.
+//!
+//! The best games are kept in a text file in the user's data directory (`$XDG_DATA_HOME/snake`,
+//! or `~/.local/share/snake`). Every line of the file holds a single game:
+//!
+//! ```text
+//! score length milliseconds date name
+//! ```
+//!
+//! The date is in seconds since the Unix epoch. Lines which cannot be read are dropped, so a
+//! corrupt file only loses the games it garbled.
+
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::PathBuf;
+
+/// The name of the directory of the game in the data directory.
+const DATA_DIR: &'static str = "snake";
+/// The name of the high-score file.
+const SCORES_FILE: &'static str = "scores";
+/// The number of scores kept.
+pub const MAX_SCORES: usize = 10;
+
+/// A game, once it is over.
+#[derive(Clone, Debug)]
+pub struct Score {
+    /// The food eaten.
+    pub score: i32,
+    /// The length of the snake at the end.
+    pub length: usize,
+    /// How long the game lasted, in milliseconds.
+    pub duration: u64,
+    /// When the game was played, in seconds since the Unix epoch.
+    pub date: u64,
+    /// The initials of the player.
+    pub name: String,
+}
+
+impl Score {
+    /// Parse a line of the high-score file.
+    fn parse(line: &str) -> Option<Score> {
+        let mut words = line.split_whitespace();
+        let score = words.next()?.parse().ok()?;
+        let length = words.next()?.parse().ok()?;
+        let duration = words.next()?.parse().ok()?;
+        let date = words.next()?.parse().ok()?;
+        let name = words.next()?.to_owned();
+
+        if words.next().is_some() || score < 0 || !is_name(&name) {
+            return None;
+        }
+
+        Some(Score {
+            score: score,
+            length: length,
+            duration: duration,
+            date: date,
+            name: name,
+        })
+    }
+}
+
+/// The high scores.
+pub struct Scores {
+    /// The scores, best first.
+    scores: Vec<Score>,
+}
+
+impl Scores {
+    /// Get the path of the high-score file.
+    ///
+    /// This is in the data directory, or the current directory if there is none.
+    fn path() -> PathBuf {
+        let mut path = env::var_os("XDG_DATA_HOME").map(PathBuf::from).or_else(|| {
+            env::var_os("HOME").map(|home| PathBuf::from(home).join(".local").join("share"))
+        }).unwrap_or_default();
+
+        path.push(DATA_DIR);
+        path.push(SCORES_FILE);
+        path
+    }
+
+    /// Load the high scores.
+    ///
+    /// A missing file means there are no scores yet, and malformed lines are skipped, so this
+    /// never fails.
+    pub fn load() -> Scores {
+        let mut scores: Vec<Score> = File::open(Scores::path()).map(|file| {
+            BufReader::new(file).lines()
+                .filter_map(|line| line.ok())
+                .filter_map(|line| Score::parse(&line))
+                .collect()
+        }).unwrap_or_default();
+
+        // Higher scores first, and older ones first among equals.
+        scores.sort_by(|a, b| b.score.cmp(&a.score).then(a.date.cmp(&b.date)));
+        scores.truncate(MAX_SCORES);
+        Scores {
+            scores: scores,
+        }
+    }
+
+    /// Write the high scores to the high-score file, creating its directory if need be.
+    pub fn save(&self) -> io::Result<()> {
+        let path = Scores::path();
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let mut file = BufWriter::new(File::create(path)?);
+        for score in self.scores.iter() {
+            writeln!(file, "{} {} {} {} {}", score.score, score.length, score.duration, score.date,
+                     score.name)?;
+        }
+
+        file.flush()
+    }
+
+    /// Would a score make it into the table?
+    pub fn qualifies(&self, score: i32) -> bool {
+        score > 0 && (self.scores.len() < MAX_SCORES
+                      || self.scores.last().map_or(true, |last| score > last.score))
+    }
+
+    /// Insert a score, dropping whatever falls off the table.
+    pub fn insert(&mut self, score: Score) {
+        let index = self.scores.iter().position(|s| s.score < score.score).unwrap_or(self.scores.len());
+        self.scores.insert(index, score);
+        self.scores.truncate(MAX_SCORES);
+    }
+
+    /// Write the table, one line per score, ending every line with _eol_.
+    pub fn write_table<W: Write>(&self, to: &mut W, eol: &str) -> io::Result<()> {
+        write!(to, "High scores:{}", eol)?;
+
+        if self.scores.is_empty() {
+            write!(to, "    no scores yet.{}", eol)?;
+        }
+        for (rank, score) in self.scores.iter().enumerate() {
+            write!(to, "    {:2}. {:3} {:>5} food, {:>5} long, {:>7}, {}{}", rank + 1, score.name,
+                   score.score, score.length, format_duration(score.duration),
+                   format_date(score.date), eol)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Is this a valid name for the table, that is, up to three letters or digits?
+pub fn is_name(name: &str) -> bool {
+    !name.is_empty() && name.len() <= 3 && name.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+/// Format a duration in milliseconds as minutes and seconds.
+pub fn format_duration(duration: u64) -> String {
+    let seconds = duration / 1000;
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+/// Format a date in seconds since the Unix epoch as year, month and day.
+pub fn format_date(date: u64) -> String {
+    // Count the years in eras of 400, starting in March, so leap days come last.
+    let days = date / 86400 + 719468;
+    let era = days / 146097;
+    let day_of_era = days % 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+
+    let (year, month) = if month < 10 {
+        (era * 400 + year_of_era, month + 3)
+    } else {
+        (era * 400 + year_of_era + 1, month - 9)
+    };
+
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
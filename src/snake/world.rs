@@ -0,0 +1,614 @@
+//! The game world.
+//!
+//! The world holds the snakes and their food, and moves them along one step at a time. It does
+//! not draw anything; instead every step reports what happened, for the front end to show.
+
+use std::collections::VecDeque;
+
+use extra::rand::Randomizer;
+
+use map::{Map, START_LENGTH};
+
+/// The speed at the start of a game, in steps per second.
+pub const START_SPEED: u64 = 10;
+/// The speed gained with every food the snake grows on.
+const SPEED_GAIN: u64 = 4;
+/// The speed the snake never goes beyond, in steps per second.
+pub const MAX_SPEED: u64 = 200;
+/// The points of bonus food.
+const BONUS_POINTS: i32 = 5;
+/// The number of steps bonus food lasts, unless eaten.
+pub const BONUS_STEPS: u32 = 60;
+/// The number of steps a slow-down lasts.
+pub const SLOW_STEPS: u32 = 100;
+/// The number of steps a ghost lasts.
+pub const GHOST_STEPS: u32 = 60;
+/// The number of body parts lost to shrinking.
+const SHRINK_PARTS: usize = 5;
+/// The length a snake never shrinks below.
+const MIN_LENGTH: usize = 3;
+
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+/// Every direction.
+pub const DIRECTIONS: [Direction; 4] = [Direction::Up, Direction::Down, Direction::Left, Direction::Right];
+
+impl Direction {
+    /// Get the opposite direction.
+    pub fn opposite(&self) -> Direction {
+        match *self {
+            Direction::Up => Direction::Down,
+            Direction::Down => Direction::Up,
+            Direction::Left => Direction::Right,
+            Direction::Right => Direction::Left,
+        }
+    }
+}
+
+/// Snake's Body Part location and direction
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub struct BodyPart {
+    pub x: u16,
+    pub y: u16,
+    pub direction: Direction,
+}
+
+/// The kinds of food.
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum FoodKind {
+    /// Worth a point, and makes the snake grow and speed up.
+    Normal,
+    /// Like normal food, but worth more, and only there for a while.
+    Bonus,
+    /// Slows the game down for a while.
+    Slow,
+    /// Makes the snake shorter.
+    Shrink,
+    /// Lets the snake pass through itself for a while.
+    Ghost,
+}
+
+/// Every kind of food.
+pub const FOOD_KINDS: [FoodKind; 5] = [FoodKind::Normal, FoodKind::Bonus, FoodKind::Slow, FoodKind::Shrink,
+                                       FoodKind::Ghost];
+
+/// How likely every kind of food is to spawn, in the order of `FOOD_KINDS`, as weights.
+pub const DEFAULT_WEIGHTS: [u32; 5] = [70, 10, 8, 6, 6];
+/// The largest spawn weight, which keeps the sum of the weights from overflowing.
+pub const MAX_WEIGHT: u32 = 10000;
+
+impl FoodKind {
+    /// Get the name of the kind of food.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            FoodKind::Normal => "normal",
+            FoodKind::Bonus => "bonus",
+            FoodKind::Slow => "slow",
+            FoodKind::Shrink => "shrink",
+            FoodKind::Ghost => "ghost",
+        }
+    }
+
+    /// Get a kind of food by its name.
+    pub fn from_name(name: &str) -> Option<FoodKind> {
+        FOOD_KINDS.iter().cloned().find(|kind| kind.name() == name)
+    }
+}
+
+/// Parse spawn weights, in the form `kind=weight,...`.
+///
+/// The kinds left out keep their default weight. Returns None if the weights are malformed,
+/// above `MAX_WEIGHT`, or all zero.
+pub fn parse_weights(spec: &str) -> Option<[u32; 5]> {
+    let mut weights = DEFAULT_WEIGHTS;
+
+    for pair in spec.split(',') {
+        let mut words = pair.splitn(2, '=');
+        let kind = FoodKind::from_name(words.next()?.trim())?;
+        let weight = words.next()?.trim().parse().ok()?;
+        if weight > MAX_WEIGHT {
+            return None;
+        }
+        weights[FOOD_KINDS.iter().position(|&k| k == kind).unwrap()] = weight;
+    }
+
+    if weights.iter().all(|&weight| weight == 0) {
+        return None;
+    }
+
+    Some(weights)
+}
+
+/// Snake's Food
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub struct Food {
+    pub x: u16,
+    pub y: u16,
+    pub kind: FoodKind,
+    /// The number of steps left before the food disappears, if it ever does.
+    pub expires: Option<u32>,
+}
+
+/// Snake
+pub struct Snake {
+    pub direction: Direction,
+    /// The body parts, from the tail to the head.
+    pub body: VecDeque<BodyPart>,
+    /// The points earned so far.
+    pub score: i32,
+    /// The number of steps left before the snake stops being a ghost, able to pass through
+    /// itself.
+    pub ghost: u32,
+}
+
+impl Snake {
+    /// Create a snake with its head at (x, y), going in the given direction, and its body trailing
+    /// behind.
+    fn new(x: u16, y: u16, direction: Direction) -> Snake {
+        let body = (0..START_LENGTH).rev().map(|i| {
+            let (x, y) = match direction {
+                Direction::Up => (x, y + i),
+                Direction::Down => (x, y - i),
+                Direction::Left => (x + i, y),
+                Direction::Right => (x - i, y),
+            };
+
+            BodyPart {
+                x: x,
+                y: y,
+                direction: direction,
+            }
+        }).collect();
+
+        Snake {
+            direction: direction,
+            body: body,
+            score: 0,
+            ghost: 0,
+        }
+    }
+
+    /// Get the head of the snake.
+    pub fn head(&self) -> &BodyPart {
+        self.body.back().unwrap()
+    }
+
+    /// Turn the snake.
+    ///
+    /// The snake cannot turn back onto itself, so turning to the opposite direction is ignored.
+    /// Returns false if the turn was ignored.
+    pub fn turn(&mut self, direction: Direction) -> bool {
+        if direction == self.direction.opposite() {
+            return false;
+        }
+
+        self.direction = direction;
+        true
+    }
+}
+
+/// Something that happened to a snake during a step, given by its index.
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum Event {
+    /// The snake ate the food.
+    Ate(usize),
+    /// The snake grew by one body part.
+    Grew(usize),
+    /// The snake lost some of its body parts.
+    Shrank(usize),
+    /// The snake ran into a wall, itself or another snake, or off the edge of the map.
+    Died(usize),
+    /// The snake filled every free cell of the map, leaving no room for more food.
+    Won(usize),
+}
+
+/// The game world.
+pub struct World {
+    /// The layout of the play area.
+    pub map: Map,
+    /// Does leaving the map on one edge enter it from the opposite one?
+    pub wrap: bool,
+    /// The snakes, one per player.
+    pub snakes: Vec<Snake>,
+    /// Snake's Food
+    pub food: Food,
+    /// The speed, in steps per second.
+    pub speed: u64,
+    /// The number of steps left before the slow-down ends.
+    pub slow: u32,
+    /// How likely every kind of food is to spawn, in the order of `FOOD_KINDS`.
+    pub weights: [u32; 5],
+    /// The randomizer
+    pub rand: Randomizer,
+}
+
+impl World {
+    /// Create a world on a map, with a snake per player at its starting position.
+    ///
+    /// There can be one or two players; the second one needs the second start of the map.
+    pub fn new(map: Map, wrap: bool, players: usize, rand: Randomizer) -> World {
+        let mut world = World {
+            map: map,
+            wrap: wrap,
+            snakes: Vec::new(),
+            food: Food {
+                x: 0,
+                y: 0,
+                kind: FoodKind::Normal,
+                expires: None,
+            },
+            speed: START_SPEED,
+            slow: 0,
+            weights: DEFAULT_WEIGHTS,
+            rand: rand,
+        };
+
+        world.snakes = world.starting_snakes(players);
+        world.reset();
+        world
+    }
+
+    /// Get the snakes of the players at their starting positions.
+    fn starting_snakes(&self, players: usize) -> Vec<Snake> {
+        let mut snakes = Vec::new();
+
+        let (x, y) = self.map.start;
+        snakes.push(Snake::new(x, y, Direction::Right));
+        if players > 1 {
+            let (x, y) = self.map.start2.unwrap();
+            snakes.push(Snake::new(x, y, Direction::Left));
+        }
+
+        snakes
+    }
+
+    /// Put the snakes back at their starting positions, and normal food in the middle (or
+    /// anywhere free, if the middle is taken).
+    ///
+    /// The randomizer and the spawn weights are left as they are.
+    pub fn reset(&mut self) {
+        let players = self.snakes.len();
+        self.snakes = self.starting_snakes(players);
+        self.speed = START_SPEED;
+        self.slow = 0;
+
+        let (x, y) = (self.map.width / 2, self.map.height / 2);
+        if self.is_free(x, y) {
+            self.food.x = x;
+            self.food.y = y;
+        } else {
+            self.move_food();
+        }
+        self.food.kind = FoodKind::Normal;
+        self.food.expires = None;
+    }
+
+    /// Get the speed the game runs at, in steps per second, slow-down included.
+    pub fn rate(&self) -> u64 {
+        if self.slow > 0 {
+            self.speed / 2
+        } else {
+            self.speed
+        }
+    }
+
+    /// Step the world: turn every snake given a direction, and move them all along.
+    ///
+    /// _turns_ holds a direction (or none) per snake. Returns what happened on the way.
+    pub fn step(&mut self, turns: &[Option<Direction>]) -> Vec<Event> {
+        let mut events = Vec::new();
+        let mut dead = vec![false; self.snakes.len()];
+        let mut ate = vec![false; self.snakes.len()];
+
+        for (snake, &turn) in self.snakes.iter_mut().zip(turns) {
+            if let Some(direction) = turn {
+                snake.turn(direction);
+            }
+
+            snake.ghost = snake.ghost.saturating_sub(1);
+        }
+
+        self.slow = self.slow.saturating_sub(1);
+        if let Some(expires) = self.food.expires {
+            if expires <= 1 {
+                self.move_food();
+            } else {
+                self.food.expires = Some(expires - 1);
+            }
+        }
+
+        for i in 0..self.snakes.len() {
+            let tail = self.snakes[i].body.pop_front().unwrap();
+            match self.next_head(i) {
+                Some(head) => {
+                    self.snakes[i].body.push_back(head);
+
+                    if (head.x, head.y) == (self.food.x, self.food.y) {
+                        // Growing is leaving the tail where it was.
+                        if self.food.kind == FoodKind::Normal || self.food.kind == FoodKind::Bonus {
+                            self.snakes[i].body.push_front(tail);
+                        }
+                        ate[i] = true;
+                    }
+                },
+                None => {
+                    // Off the edge, where there is nothing to move to.
+                    self.snakes[i].body.push_front(tail);
+                    dead[i] = true;
+                },
+            }
+        }
+
+        // The snakes are only checked once they have all moved, so running into each other
+        // head-on kills both.
+        for i in 0..self.snakes.len() {
+            if dead[i] || self.is_dead(i) {
+                dead[i] = true;
+                events.push(Event::Died(i));
+            }
+        }
+
+        for i in 0..self.snakes.len() {
+            if ate[i] && !dead[i] {
+                events.push(Event::Ate(i));
+                self.eat(i, &mut events);
+                if !self.move_food() {
+                    events.push(Event::Won(i));
+                }
+            }
+        }
+
+        events
+    }
+
+    /// Let a snake have the effect of the food it ate.
+    fn eat(&mut self, snake: usize, events: &mut Vec<Event>) {
+        match self.food.kind {
+            FoodKind::Normal | FoodKind::Bonus => {
+                self.snakes[snake].score += if self.food.kind == FoodKind::Bonus { BONUS_POINTS } else { 1 };
+                self.speed = (self.speed + SPEED_GAIN).min(MAX_SPEED);
+                events.push(Event::Grew(snake));
+            },
+            FoodKind::Slow => self.slow = SLOW_STEPS,
+            FoodKind::Shrink => {
+                let body = &mut self.snakes[snake].body;
+                let parts = SHRINK_PARTS.min(body.len().saturating_sub(MIN_LENGTH));
+                body.drain(..parts);
+                events.push(Event::Shrank(snake));
+            },
+            FoodKind::Ghost => self.snakes[snake].ghost = GHOST_STEPS,
+        }
+    }
+
+    /// Get the body part the head of a snake moves to next.
+    ///
+    /// Returns None if the head leaves the map, and the map does not wrap around.
+    fn next_head(&self, snake: usize) -> Option<BodyPart> {
+        let snake = &self.snakes[snake];
+        let head = snake.head();
+        let (x, y) = self.neighbour(head.x, head.y, snake.direction)?;
+
+        Some(BodyPart {
+            x: x,
+            y: y,
+            direction: snake.direction,
+        })
+    }
+
+    /// Get the cell next to (x, y) in the given direction.
+    ///
+    /// Returns None if that is off the map, and the map does not wrap around.
+    pub fn neighbour(&self, x: u16, y: u16, direction: Direction) -> Option<(u16, u16)> {
+        let (width, height) = (self.map.width, self.map.height);
+
+        let (x, y) = if self.wrap {
+            match direction {
+                Direction::Up => (x, (y + height - 1) % height),
+                Direction::Down => (x, (y + 1) % height),
+                Direction::Left => ((x + width - 1) % width, y),
+                Direction::Right => ((x + 1) % width, y),
+            }
+        } else {
+            match direction {
+                Direction::Up => (x, y.checked_sub(1)?),
+                Direction::Down => (x, y + 1),
+                Direction::Left => (x.checked_sub(1)?, y),
+                Direction::Right => (x + 1, y),
+            }
+        };
+
+        if x >= width || y >= height {
+            return None;
+        }
+
+        Some((x, y))
+    }
+
+    /// Is (x, y) part of any snake?
+    pub fn is_snake(&self, x: u16, y: u16) -> bool {
+        self.snakes.iter().any(|snake| snake.body.iter().any(|part| (x, y) == (part.x, part.y)))
+    }
+
+    /// Is (x, y) neither a wall nor part of a snake?
+    pub fn is_free(&self, x: u16, y: u16) -> bool {
+        !self.map.is_wall(x, y) && !self.is_snake(x, y)
+    }
+
+    /// Is a snake's head overlapping a wall or a body part, of its own (unless it is a ghost) or
+    /// of another snake?
+    pub fn is_dead(&self, snake: usize) -> bool {
+        let head = self.snakes[snake].head();
+        let overlaps = self.snakes.iter().enumerate()
+            .filter(|&(i, other)| i != snake || other.ghost == 0)
+            .flat_map(|(_, other)| other.body.iter())
+            .filter(|part| (head.x, head.y) == (part.x, part.y))
+            .count();
+
+        // The head always overlaps itself, unless it is left out along with the rest of a ghost.
+        let itself = if self.snakes[snake].ghost == 0 { 1 } else { 0 };
+        overlaps > itself || self.map.is_wall(head.x, head.y)
+    }
+
+    /// Replace the snake's food by a random kind of food, in a random free cell.
+    ///
+    /// Returns false if there is no free cell left, in which case the food stays where it is.
+    fn move_food(&mut self) -> bool {
+        let total: u32 = self.weights.iter().sum();
+        let mut r = (self.rand.read_u8() as u32) << 8 | self.rand.read_u8() as u32;
+        r %= total;
+        for (&kind, &weight) in FOOD_KINDS.iter().zip(self.weights.iter()) {
+            if r < weight {
+                self.food.kind = kind;
+                break;
+            }
+            r -= weight;
+        }
+        self.food.expires = if self.food.kind == FoodKind::Bonus { Some(BONUS_STEPS) } else { None };
+
+        let mut free = Vec::new();
+        for y in 0..self.map.height {
+            for x in 0..self.map.width {
+                if self.is_free(x, y) {
+                    free.push((x, y));
+                }
+            }
+        }
+
+        if free.is_empty() {
+            return false;
+        }
+
+        let mut r = 0;
+        for _ in 0..4 {
+            r = r << 8 | self.rand.read_u8() as usize;
+        }
+
+        let (x, y) = free[r % free.len()];
+        self.food.x = x;
+        self.food.y = y;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Create a one-player world in a walled 20x8 area.
+    ///
+    /// The snake starts at the top, heading right with its head at (17, 2), three cells away
+    /// from the right wall. The food is in the middle, out of its way.
+    fn world() -> World {
+        let world = World::new(Map::bordered(20, 8).unwrap(), false, 1, Randomizer::new(0));
+        assert_eq!(world.map.start, (17, 2));
+        assert_eq!((world.food.x, world.food.y), (10, 4));
+        world
+    }
+
+    #[test]
+    fn running_into_a_wall() {
+        let mut world = world();
+        assert_eq!(world.step(&[None]), vec![]);
+        assert_eq!(world.step(&[None]), vec![Event::Died(0)]);
+    }
+
+    #[test]
+    fn running_into_itself() {
+        let mut world = world();
+        assert_eq!(world.step(&[Some(Direction::Down)]), vec![]);
+        assert_eq!(world.step(&[Some(Direction::Left)]), vec![]);
+        assert_eq!(world.step(&[Some(Direction::Up)]), vec![Event::Died(0)]);
+    }
+
+    #[test]
+    fn eating_grows() {
+        let mut world = world();
+        world.food = Food {
+            x: 18,
+            y: 2,
+            kind: FoodKind::Normal,
+            expires: None,
+        };
+
+        assert_eq!(world.step(&[None]), vec![Event::Ate(0), Event::Grew(0)]);
+        assert_eq!(world.snakes[0].body.len(), START_LENGTH as usize + 1);
+        assert_eq!(world.snakes[0].score, 1);
+        assert_eq!(world.speed, START_SPEED + SPEED_GAIN);
+        assert!((world.food.x, world.food.y) != (18, 2));
+    }
+
+    #[test]
+    fn filling_the_map_wins() {
+        // Coil a snake through every free cell of a walled 14x5 area but the last one, and put
+        // the food there.
+        let mut world = World::new(Map::bordered(14, 5).unwrap(), false, 1, Randomizer::new(0));
+        let mut body = VecDeque::new();
+        for y in 1..4 {
+            for i in 0..12 {
+                let (x, direction) = if y % 2 == 1 {
+                    (1 + i, Direction::Right)
+                } else {
+                    (12 - i, Direction::Left)
+                };
+                body.push_back(BodyPart {
+                    x: x,
+                    y: y,
+                    direction: direction,
+                });
+            }
+        }
+        let last = body.pop_back().unwrap();
+        world.snakes[0].body = body;
+        world.snakes[0].direction = Direction::Right;
+        world.food = Food {
+            x: last.x,
+            y: last.y,
+            kind: FoodKind::Normal,
+            expires: None,
+        };
+
+        assert_eq!(world.step(&[None]), vec![Event::Ate(0), Event::Grew(0), Event::Won(0)]);
+        assert_eq!(world.snakes[0].body.len(), 36);
+    }
+
+    #[test]
+    fn weights_are_capped() {
+        assert_eq!(parse_weights("normal=10000,bonus=10000,slow=10000,shrink=10000,ghost=10000"),
+                   Some([MAX_WEIGHT; 5]));
+        assert_eq!(parse_weights("normal=10001"), None);
+        assert_eq!(parse_weights("normal=4294967295,bonus=1"), None);
+        assert_eq!(parse_weights("normal=0,bonus=0,slow=0,shrink=0,ghost=0"), None);
+    }
+
+    #[test]
+    fn speed_is_capped() {
+        let mut world = world();
+        world.speed = MAX_SPEED - 1;
+        world.food = Food {
+            x: 18,
+            y: 2,
+            kind: FoodKind::Normal,
+            expires: None,
+        };
+
+        world.step(&[None]);
+        assert_eq!(world.speed, MAX_SPEED);
+    }
+
+    #[test]
+    fn turning_back_is_ignored() {
+        let mut world = world();
+        assert!(!world.snakes[0].turn(Direction::Right.opposite()));
+
+        assert_eq!(world.step(&[Some(Direction::Left)]), vec![]);
+        assert_eq!(world.snakes[0].direction, Direction::Right);
+        let head = *world.snakes[0].head();
+        assert_eq!((head.x, head.y), (18, 2));
+    }
+}